@@ -1,21 +1,195 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use bytesize::ByteSize;
+use clap::{Parser, Subcommand, ValueEnum};
+use nanum_core::types::TokenScope;
+use time::Duration;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
     #[command(subcommand)]
     pub command: Command,
-    /// S3 bucket name to use
+    /// S3 bucket name to use. Required by every command except `keygen`
     #[arg(env = "S3_BUCKET_NAME", long, short)]
-    pub bucket: String,
+    pub bucket: Option<String>,
 }
 
 #[derive(Subcommand)]
 pub enum Command {
     /// List uploaded file IDs [alias: ls]
     #[command(alias = "ls")]
-    List,
+    List {
+        /// Only shares created by this email
+        #[arg(long)]
+        creator: Option<String>,
+        /// Only shares created longer ago than this, like `30d` or `12h`. Shares created before
+        /// creation times were recorded are included
+        #[arg(long, value_parser = parse_duration)]
+        older_than: Option<Duration>,
+        /// Only shares larger than this, like `100MB`
+        #[arg(long)]
+        larger_than: Option<ByteSize>,
+        /// Only shares whose upload was not completed
+        #[arg(long)]
+        incomplete: bool,
+        #[arg(long, value_enum, default_value_t = SortKey::Id)]
+        sort: SortKey,
+        #[arg(long, short, value_enum, default_value_t = OutputFormat::Table)]
+        output: OutputFormat,
+    },
     /// Delete uploaded files [alias: rm]
     #[command(alias = "rm")]
     Delete { ids: Vec<String> },
+    /// Show the metadata and stored chunks of a share
+    Show { id: String },
+    /// Set when a share expires
+    SetExpiry {
+        id: String,
+        /// Time from now, like `7d`. `0s` expires the share immediately
+        #[arg(value_parser = parse_duration, required_unless_present = "never")]
+        expires_in: Option<Duration>,
+        /// Never expire the share
+        #[arg(long, conflicts_with = "expires_in")]
+        never: bool,
+    },
+    /// Block downloads of a share without deleting it
+    Disable { id: String },
+    /// Allow downloads of a disabled share again
+    Enable { id: String },
+    /// Copy shares, still encrypted, to a directory or another bucket
+    Export {
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Directory, or `s3://<bucket>`
+        #[arg(long)]
+        to: String,
+        /// Region of the bucket given by `--to`, if it differs
+        #[arg(long)]
+        region: Option<String>,
+    },
+    /// Copy shares from a directory or another bucket, as written by `export`
+    Import {
+        /// Every share in the source if empty
+        ids: Vec<String>,
+        /// Directory, or `s3://<bucket>`
+        #[arg(long)]
+        from: String,
+        /// Region of the bucket given by `--from`, if it differs
+        #[arg(long)]
+        region: Option<String>,
+    },
+    /// Rebuild the share index from every metadata
+    Reindex,
+    /// Report stored bytes and shares per user, from the sizes of the objects in the bucket
+    Usage {
+        /// Number of largest shares to show per user
+        #[arg(long, default_value_t = 3)]
+        top: usize,
+        #[arg(long, short, value_enum, default_value_t = OutputFormat::Table)]
+        output: OutputFormat,
+    },
+    /// Find chunks without metadata and metadata with missing chunks. Only reports them unless
+    /// `--delete` is given
+    Gc {
+        /// Delete what was found instead of only reporting it
+        #[arg(long)]
+        delete: bool,
+        /// Skip objects modified more recently than this, which may belong to uploads in progress
+        #[arg(long, value_parser = parse_duration, default_value = "1d")]
+        grace: Duration,
+    },
+    /// Revoke all current sessions and API tokens of users
+    Revoke { emails: Vec<String> },
+    /// Manage personal API tokens
+    #[command(subcommand)]
+    Token(TokenCommand),
+    /// Generate a key pair for receiving files encrypted to public keys
+    Keygen,
+    /// Decrypt a file encrypted to public keys
+    Decrypt {
+        id: String,
+        /// File containing the private key, like the output of `keygen`
+        #[arg(long, short)]
+        identity: PathBuf,
+        /// Path to write the decrypted file to. Defaults to its original filename
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TokenCommand {
+    /// List personal API tokens [alias: ls]
+    #[command(alias = "ls")]
+    List,
+    /// Create a personal API token on behalf of a user
+    Create {
+        /// Email of the user owning the token
+        #[arg(long)]
+        email: String,
+        /// Name to identify the token
+        #[arg(long)]
+        name: String,
+        /// Scopes granted to the token
+        #[arg(long = "scope", value_enum, default_values_t = [Scope::Upload])]
+        scopes: Vec<Scope>,
+        /// Days until the token expires
+        #[arg(long, default_value_t = 30)]
+        expires_in_days: u32,
+    },
+    /// Delete personal API tokens [alias: rm]
+    #[command(alias = "rm")]
+    Delete { ids: Vec<String> },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SortKey {
+    Id,
+    Creator,
+    /// Oldest first
+    Created,
+    /// Largest first
+    Size,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Parses durations like `90s`, `30m`, `12h`, `7d` or `2w`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let unit_start = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or("missing unit. Use one of s, m, h, d and w")?;
+    let (value, unit) = s.split_at(unit_start);
+    let value = value
+        .parse::<i64>()
+        .map_err(|error| format!("invalid number: {error}"))?;
+    match unit {
+        "s" => Ok(Duration::seconds(value)),
+        "m" => Ok(Duration::minutes(value)),
+        "h" => Ok(Duration::hours(value)),
+        "d" => Ok(Duration::days(value)),
+        "w" => Ok(Duration::weeks(value)),
+        _ => Err(format!(
+            "unknown unit `{unit}`. Use one of s, m, h, d and w"
+        )),
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Scope {
+    Upload,
+}
+
+impl From<Scope> for TokenScope {
+    fn from(scope: Scope) -> Self {
+        match scope {
+            Scope::Upload => TokenScope::Upload,
+        }
+    }
 }
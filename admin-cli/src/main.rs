@@ -1,42 +1,177 @@
 mod cli;
 mod s3;
+mod store;
 
-use anyhow::Result;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use aead::{
+    generic_array::{typenum::Unsigned, GenericArray},
+    stream::DecryptorBE32,
+    Aead, AeadCore, KeyInit,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use aws_sdk_s3::types::Object;
 use bytesize::ByteSize;
+use chacha20poly1305::{Key, XChaCha20Poly1305};
 use clap::Parser;
 use cli_table::{
     format::{Border, Justify, Separator},
     Cell, Table,
 };
 use futures_util::{stream::FuturesUnordered, TryStreamExt};
+use hkdf::Hkdf;
+use nanum_core::{
+    key_check,
+    recipient::{self, Identity},
+    types::{ApiToken, FileInfo, IndexEntry, Metadata, ShareIndex},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+use crate::{
+    cli::{Args, Command, OutputFormat, SortKey, TokenCommand},
+    store::Store,
+};
 
-use crate::cli::{Args, Command};
+fn format_timestamp(timestamp: i64) -> Result<String> {
+    Ok(OffsetDateTime::from_unix_timestamp(timestamp)?.format(&Rfc3339)?)
+}
+
+/// Only the padded size is known without the key.
+fn format_size(entry: &IndexEntry) -> String {
+    let size = ByteSize(entry.size as u64);
+    if entry.padding.is_some() {
+        format!("{size} (padded)")
+    } else {
+        size.to_string()
+    }
+}
 
-async fn list(s3_client: &aws_sdk_s3::Client, bucket: &str) -> Result<()> {
-    let metadatas = s3::list_metadatas(s3_client, bucket).await?;
-    let table = metadatas
+fn build_index(metadatas: Vec<(String, Metadata)>) -> ShareIndex {
+    let shares = metadatas
         .into_iter()
-        .map(|(id, metadata)| {
-            vec![
+        .map(|(id, metadata)| (id, metadata.index_entry()))
+        .collect();
+    ShareIndex { shares }
+}
+
+/// Filters of the `list` command.
+struct ListFilter {
+    creator: Option<String>,
+    older_than: Option<Duration>,
+    larger_than: Option<ByteSize>,
+    incomplete: bool,
+}
+
+impl ListFilter {
+    fn matches(&self, entry: &IndexEntry, now: OffsetDateTime) -> bool {
+        self.creator
+            .as_ref()
+            .is_none_or(|creator| entry.creator_email == *creator)
+            && self.older_than.is_none_or(|older_than| {
+                entry
+                    .created_at
+                    .is_none_or(|created_at| created_at <= (now - older_than).unix_timestamp())
+            })
+            && self
+                .larger_than
+                .is_none_or(|larger_than| entry.size as u64 > larger_than.as_u64())
+            && (!self.incomplete || !entry.completed)
+    }
+}
+
+#[derive(Serialize)]
+struct ListedShare<'a> {
+    id: &'a str,
+    #[serde(flatten)]
+    entry: &'a IndexEntry,
+}
+
+fn format_optional_timestamp(timestamp: Option<i64>) -> Result<String> {
+    Ok(timestamp
+        .map(format_timestamp)
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// Quotes a CSV field if needed, as in RFC 4180.
+fn csv_field(field: &str) -> String {
+    if field.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn print_csv(shares: &[(String, IndexEntry)]) -> Result<()> {
+    println!(
+        "id,creator_email,size,block_size,padded,created_at,completed_at,expires_at,completed"
+    );
+    for (id, entry) in shares {
+        println!(
+            "{},{},{},{},{},{},{},{},{}",
+            csv_field(id),
+            csv_field(&entry.creator_email),
+            entry.size,
+            entry.block_size,
+            entry.padding.is_some(),
+            format_optional_timestamp(entry.created_at)?,
+            format_optional_timestamp(entry.completed_at)?,
+            format_optional_timestamp(entry.expires_at)?,
+            entry.completed,
+        );
+    }
+    Ok(())
+}
+
+fn print_table(shares: Vec<(String, IndexEntry)>) -> Result<()> {
+    let table = shares
+        .into_iter()
+        .map(|(id, entry)| {
+            let size = format_size(&entry);
+            Ok(vec![
                 id.cell(),
-                metadata.creator_email.cell(),
-                ByteSize(metadata.size as u64)
+                entry.creator_email.cell(),
+                size.cell().justify(Justify::Right),
+                ByteSize(entry.block_size as u64)
                     .to_string()
                     .cell()
                     .justify(Justify::Right),
-                ByteSize(metadata.block_size as u64)
-                    .to_string()
-                    .cell()
-                    .justify(Justify::Right),
-            ]
+                entry
+                    .created_at
+                    .map(format_timestamp)
+                    .transpose()?
+                    .unwrap_or_else(|| "unknown".to_string())
+                    .cell(),
+                entry
+                    .completed_at
+                    .map(format_timestamp)
+                    .transpose()?
+                    .unwrap_or_else(|| {
+                        if entry.completed {
+                            "unknown".to_string()
+                        } else {
+                            "incomplete".to_string()
+                        }
+                    })
+                    .cell(),
+            ])
         })
-        .collect::<Vec<_>>()
+        .collect::<Result<Vec<_>>>()?
         .table()
         .title(vec![
             "ID".cell(),
             "CREATOR EMAIL".cell(),
             "SIZE".cell(),
             "BLOCK SIZE".cell(),
+            "CREATED AT".cell(),
+            "COMPLETED AT".cell(),
         ])
         .separator(
             Separator::builder()
@@ -50,12 +185,691 @@ async fn list(s3_client: &aws_sdk_s3::Client, bucket: &str) -> Result<()> {
     Ok(())
 }
 
+async fn list(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    filter: &ListFilter,
+    sort: SortKey,
+    output: OutputFormat,
+) -> Result<()> {
+    // read every metadata rather than the index, which may be out of date
+    let index = build_index(s3::list_metadatas(s3_client, bucket).await?);
+
+    let now = OffsetDateTime::now_utc();
+    let mut shares = index
+        .shares
+        .into_iter()
+        .filter(|(_, entry)| filter.matches(entry, now))
+        .collect::<Vec<_>>();
+    // shares come sorted by ID
+    match sort {
+        SortKey::Id => {}
+        SortKey::Creator => shares.sort_by(|(_, a), (_, b)| a.creator_email.cmp(&b.creator_email)),
+        SortKey::Created => shares.sort_by_key(|(_, entry)| entry.created_at),
+        SortKey::Size => shares.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.size)),
+    }
+
+    match output {
+        OutputFormat::Table => print_table(shares)?,
+        OutputFormat::Json => {
+            let shares = shares
+                .iter()
+                .map(|(id, entry)| ListedShare { id, entry })
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&shares)?);
+        }
+        OutputFormat::Csv => print_csv(&shares)?,
+    }
+    Ok(())
+}
+
 async fn delete(s3_client: &aws_sdk_s3::Client, bucket: &str, ids: &[String]) -> Result<()> {
     ids.iter()
         .map(|id| async move {
             s3::delete_metadata(s3_client, bucket, id).await?;
             s3::delete_file(s3_client, bucket, id).await?;
             println!("{id} deleted");
+            anyhow::Ok(())
+        })
+        .collect::<FuturesUnordered<_>>()
+        .try_collect::<()>()
+        .await?;
+    s3::update_index(s3_client, bucket, |index| {
+        for id in ids {
+            index.shares.remove(id);
+        }
+    })
+    .await
+}
+
+async fn show(s3_client: &aws_sdk_s3::Client, bucket: &str, id: &str) -> Result<()> {
+    let metadata = s3::get_metadata(s3_client, bucket, id)
+        .await?
+        .with_context(|| format!("{id} not found"))?;
+    let metadata_key = format!("metadata/{id}.json");
+    let metadata_size = object_size(
+        s3::list_objects(s3_client, bucket, &metadata_key)
+            .await?
+            .iter()
+            .filter(|object| object.key() == Some(metadata_key.as_str())),
+    );
+    let chunks = s3::list_objects(s3_client, bucket, &format!("file/{id}."))
+        .await?
+        .into_iter()
+        .filter_map(|object| {
+            let (chunk_id, seq) = parse_chunk_key(object.key()?)?;
+            (chunk_id == id).then_some((seq, object))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    println!("{}", serde_json::to_string_pretty(&metadata)?);
+    println!();
+
+    let seq_count = metadata.chunk_count();
+    let missing = (1..=seq_count)
+        .filter(|seq| !chunks.contains_key(seq))
+        .map(|seq| seq.to_string())
+        .collect::<Vec<_>>();
+    let extra = chunks.range(seq_count + 1..).count();
+    println!("chunks:   {} of {seq_count}", seq_count - missing.len());
+    if !missing.is_empty() {
+        println!("missing:  {}", missing.join(", "));
+    }
+    if extra > 0 {
+        println!("extra:    {extra} chunks after the last one");
+    }
+    println!(
+        "stored:   {}",
+        ByteSize(metadata_size + object_size(chunks.values()))
+    );
+    let upload = match (&metadata.info, metadata.completed_at) {
+        (Some(_), Some(completed_at)) => {
+            format!("completed at {}", format_timestamp(completed_at)?)
+        }
+        (Some(_), None) => "completed".to_string(),
+        (None, _) => "incomplete".to_string(),
+    };
+    println!("upload:   {upload}");
+    let expires = match metadata.expires_at {
+        Some(expires_at) => format_timestamp(expires_at)?,
+        None => "never".to_string(),
+    };
+    println!("expires:  {expires}");
+    let status = match metadata.disabled_at {
+        Some(disabled_at) => format!("disabled at {}", format_timestamp(disabled_at)?),
+        None => "enabled".to_string(),
+    };
+    println!("status:   {status}");
+    Ok(())
+}
+
+async fn set_expiry(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    id: &str,
+    expires_in: Option<Duration>,
+) -> Result<()> {
+    let expires_at =
+        expires_in.map(|expires_in| (OffsetDateTime::now_utc() + expires_in).unix_timestamp());
+    let metadata = s3::update_metadata(s3_client, bucket, id, |metadata| {
+        metadata.expires_at = expires_at;
+    })
+    .await?
+    .with_context(|| format!("{id} not found"))?;
+    s3::update_index(s3_client, bucket, |index| {
+        index.shares.insert(id.to_string(), metadata.index_entry());
+    })
+    .await?;
+    match expires_at {
+        Some(expires_at) => println!("{id} expires at {}", format_timestamp(expires_at)?),
+        None => println!("{id} never expires"),
+    }
+    Ok(())
+}
+
+async fn set_disabled(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    id: &str,
+    disabled: bool,
+) -> Result<()> {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    s3::update_metadata(s3_client, bucket, id, |metadata| {
+        if disabled {
+            metadata.disabled_at.get_or_insert(now);
+        } else {
+            metadata.disabled_at = None;
+        }
+    })
+    .await?
+    .with_context(|| format!("{id} not found"))?;
+    if disabled {
+        println!("{id} disabled");
+    } else {
+        println!("{id} enabled");
+    }
+    Ok(())
+}
+
+/// Checks that every chunk of a share is stored with the size of its ciphertext.
+fn check_chunks(id: &str, metadata: &Metadata, chunk_sizes: &BTreeMap<usize, u64>) -> Result<()> {
+    let tag_size = <XChaCha20Poly1305 as AeadCore>::TagSize::USIZE;
+    let seq_count = metadata.chunk_count();
+    for seq in 1..=seq_count {
+        let plaintext_size = if seq < seq_count {
+            metadata.block_size
+        } else {
+            metadata.size % metadata.block_size
+        };
+        let expected = (plaintext_size + tag_size) as u64;
+        match chunk_sizes.get(&seq) {
+            None => bail!("chunk {seq} of {id} not found"),
+            Some(&size) if size != expected => {
+                bail!("chunk {seq} of {id} is {size} bytes, expected {expected} bytes")
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Copies shares verbatim. The metadata is copied last, so that a share is not visible at the
+/// destination until every chunk is.
+async fn transfer(from: &Store, to: &Store, ids: &[String]) -> Result<()> {
+    for id in ids {
+        nanum_core::id::validate(id).with_context(|| format!("invalid ID {id}"))?;
+        let metadata_key = format!("metadata/{id}.json");
+        let metadata_body = from
+            .get(&metadata_key)
+            .await?
+            .with_context(|| format!("{id} not found"))?;
+        let metadata = serde_json::from_slice::<Metadata>(&metadata_body)
+            .with_context(|| format!("failed to parse metadata of {id}"))?;
+        if to.get(&metadata_key).await?.is_some() {
+            bail!("{id} already exists at the destination");
+        }
+        check_chunks(id, &metadata, &from.chunk_sizes(id).await?)?;
+
+        let seq_count = metadata.chunk_count();
+        let mut size = metadata_body.len() as u64;
+        for seq in 1..=seq_count {
+            let key = format!("file/{id}.{seq}");
+            let chunk = from
+                .get(&key)
+                .await?
+                .with_context(|| format!("chunk {seq} of {id} not found"))?;
+            size += chunk.len() as u64;
+            to.put(&key, chunk).await?;
+        }
+        check_chunks(id, &metadata, &to.chunk_sizes(id).await?)
+            .with_context(|| format!("failed to copy chunks of {id}"))?;
+        to.put(&metadata_key, metadata_body).await?;
+        if let Store::Bucket { client, bucket } = to {
+            s3::update_index(client, bucket, |index| {
+                index.shares.insert(id.clone(), metadata.index_entry());
+            })
+            .await?;
+        }
+        println!("{id} copied. {seq_count} chunks, {}", ByteSize(size));
+    }
+    Ok(())
+}
+
+/// Client for a bucket given to `export` or `import`, which may be in another region.
+async fn client_for_region(
+    s3_client: &aws_sdk_s3::Client,
+    region: Option<String>,
+) -> aws_sdk_s3::Client {
+    match region {
+        Some(region) => {
+            let aws_config = aws_config::from_env()
+                .region(aws_sdk_s3::config::Region::new(region))
+                .load()
+                .await;
+            aws_sdk_s3::Client::new(&aws_config)
+        }
+        None => s3_client.clone(),
+    }
+}
+
+async fn reindex(s3_client: &aws_sdk_s3::Client, bucket: &str) -> Result<()> {
+    let index = build_index(s3::list_metadatas(s3_client, bucket).await?);
+    s3::upload_index(s3_client, bucket, &index).await?;
+    println!("{} shares indexed", index.shares.len());
+    Ok(())
+}
+
+/// Splits `file/{id}.{seq}` into the share ID and the sequence number.
+fn parse_chunk_key(key: &str) -> Option<(&str, usize)> {
+    let (id, seq) = key.strip_prefix("file/")?.rsplit_once('.')?;
+    Some((id, seq.parse().ok()?))
+}
+
+/// Objects found by `gc`.
+struct Garbage {
+    id: String,
+    problem: String,
+    /// Whether the whole share is deleted, rather than only `keys`
+    delete_share: bool,
+    keys: Vec<String>,
+    size: u64,
+}
+
+fn object_size<'a>(objects: impl IntoIterator<Item = &'a Object>) -> u64 {
+    objects
+        .into_iter()
+        .map(|object| object.size().max(0) as u64)
+        .sum()
+}
+
+async fn gc(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    grace: Duration,
+    delete: bool,
+) -> Result<()> {
+    let cutoff = (OffsetDateTime::now_utc() - grace).unix_timestamp();
+    // objects modified after the cutoff may belong to uploads in progress or being deleted
+    let is_settled = |object: &Object| {
+        object
+            .last_modified()
+            .is_none_or(|modified_at| modified_at.secs() <= cutoff)
+    };
+
+    let metadata_objects = s3::list_objects(s3_client, bucket, "metadata/")
+        .await?
+        .into_iter()
+        .filter_map(|object| {
+            let id = object
+                .key()?
+                .strip_prefix("metadata/")?
+                .strip_suffix(".json")?
+                .to_string();
+            Some((id, object))
+        })
+        .collect::<HashMap<_, _>>();
+    let metadatas = s3::list_metadatas(s3_client, bucket)
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let mut chunks = BTreeMap::<String, BTreeMap<usize, Object>>::new();
+    for object in s3::list_objects(s3_client, bucket, "file/").await? {
+        if let Some((id, seq)) = object.key().and_then(parse_chunk_key) {
+            chunks
+                .entry(id.to_string())
+                .or_default()
+                .insert(seq, object);
+        }
+    }
+
+    let mut garbages = Vec::new();
+    for (id, metadata) in &metadatas {
+        let metadata_object = match metadata_objects.get(id) {
+            Some(object) => object,
+            None => continue,
+        };
+        let empty = BTreeMap::new();
+        let share_chunks = chunks.get(id).unwrap_or(&empty);
+        let seq_count = metadata.chunk_count();
+        let missing = (1..=seq_count)
+            .filter(|seq| !share_chunks.contains_key(seq))
+            .count();
+        if missing > 0 {
+            if is_settled(metadata_object) && share_chunks.values().all(is_settled) {
+                garbages.push(Garbage {
+                    id: id.clone(),
+                    problem: format!("{missing} of {seq_count} chunks missing"),
+                    delete_share: true,
+                    keys: std::iter::once(metadata_object)
+                        .chain(share_chunks.values())
+                        .filter_map(|object| object.key().map(str::to_string))
+                        .collect(),
+                    size: object_size(
+                        std::iter::once(metadata_object).chain(share_chunks.values()),
+                    ),
+                });
+            }
+            continue;
+        }
+        let extra = share_chunks
+            .range(seq_count + 1..)
+            .map(|(_, object)| object)
+            .filter(|object| is_settled(object))
+            .collect::<Vec<_>>();
+        if !extra.is_empty() {
+            garbages.push(Garbage {
+                id: id.clone(),
+                problem: format!("{} chunks after the last one", extra.len()),
+                delete_share: false,
+                keys: extra
+                    .iter()
+                    .filter_map(|object| object.key().map(str::to_string))
+                    .collect(),
+                size: object_size(extra),
+            });
+        }
+    }
+    for (id, share_chunks) in &chunks {
+        if metadata_objects.contains_key(id) || !share_chunks.values().all(is_settled) {
+            continue;
+        }
+        garbages.push(Garbage {
+            id: id.clone(),
+            problem: format!("{} chunks without metadata", share_chunks.len()),
+            delete_share: false,
+            keys: share_chunks
+                .values()
+                .filter_map(|object| object.key().map(str::to_string))
+                .collect(),
+            size: object_size(share_chunks.values()),
+        });
+    }
+    // metadata that failed to parse is neither garbage nor its chunks orphans
+    for id in metadata_objects.keys() {
+        if !metadatas.contains_key(id) {
+            eprintln!("failed to read metadata of {id}. Skipped");
+        }
+    }
+
+    if garbages.is_empty() {
+        println!("nothing to collect");
+        return Ok(());
+    }
+    garbages.sort_by(|a, b| a.id.cmp(&b.id));
+    let object_count = garbages
+        .iter()
+        .map(|garbage| garbage.keys.len())
+        .sum::<usize>();
+    let total_size = ByteSize(garbages.iter().map(|garbage| garbage.size).sum());
+    let table = garbages
+        .iter()
+        .map(|garbage| {
+            vec![
+                garbage.id.as_str().cell(),
+                garbage.problem.as_str().cell(),
+                garbage.keys.len().cell().justify(Justify::Right),
+                ByteSize(garbage.size)
+                    .to_string()
+                    .cell()
+                    .justify(Justify::Right),
+            ]
+        })
+        .collect::<Vec<_>>()
+        .table()
+        .title(vec![
+            "ID".cell(),
+            "PROBLEM".cell(),
+            "OBJECTS".cell(),
+            "SIZE".cell(),
+        ])
+        .separator(
+            Separator::builder()
+                .column(None)
+                .row(None)
+                .title(None)
+                .build(),
+        )
+        .border(Border::builder().build());
+    cli_table::print_stdout(table)?;
+
+    if !delete {
+        println!("{object_count} objects, {total_size} in total. Pass --delete to delete them");
+        return Ok(());
+    }
+    garbages
+        .iter()
+        .map(|garbage| async move {
+            if garbage.delete_share {
+                s3::delete_metadata(s3_client, bucket, &garbage.id).await?;
+                s3::delete_file(s3_client, bucket, &garbage.id).await?;
+            } else {
+                for key in &garbage.keys {
+                    s3::delete_object(s3_client, bucket, key).await?;
+                }
+            }
+            anyhow::Ok(())
+        })
+        .collect::<FuturesUnordered<_>>()
+        .try_collect::<()>()
+        .await?;
+    s3::update_index(s3_client, bucket, |index| {
+        for garbage in &garbages {
+            if garbage.delete_share {
+                index.shares.remove(&garbage.id);
+            }
+        }
+    })
+    .await?;
+    println!("{object_count} objects, {total_size} in total deleted");
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct LargeShare {
+    id: String,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct Usage {
+    /// `None` for objects whose metadata is missing or unreadable
+    creator_email: Option<String>,
+    shares: usize,
+    stored_bytes: u64,
+    largest_shares: Vec<LargeShare>,
+}
+
+async fn usage(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    top: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let creators = s3::list_metadatas(s3_client, bucket)
+        .await?
+        .into_iter()
+        .map(|(id, metadata)| (id, metadata.creator_email))
+        .collect::<HashMap<_, _>>();
+
+    // stored bytes of each share, including its metadata
+    let mut share_sizes = HashMap::<String, u64>::new();
+    let metadata_objects = s3::list_objects(s3_client, bucket, "metadata/").await?;
+    let chunk_objects = s3::list_objects(s3_client, bucket, "file/").await?;
+    for object in metadata_objects.iter().chain(&chunk_objects) {
+        let id = object.key().and_then(|key| {
+            key.strip_prefix("metadata/")
+                .and_then(|name| name.strip_suffix(".json"))
+                .or_else(|| parse_chunk_key(key).map(|(id, _)| id))
+        });
+        if let Some(id) = id {
+            *share_sizes.entry(id.to_string()).or_default() += object.size().max(0) as u64;
+        }
+    }
+
+    let mut usages = HashMap::<Option<String>, Usage>::new();
+    for (id, size) in share_sizes {
+        let creator_email = creators.get(&id).cloned();
+        let usage = usages
+            .entry(creator_email.clone())
+            .or_insert_with(|| Usage {
+                creator_email,
+                shares: 0,
+                stored_bytes: 0,
+                largest_shares: Vec::new(),
+            });
+        usage.shares += 1;
+        usage.stored_bytes += size;
+        usage.largest_shares.push(LargeShare { id, size });
+    }
+    let mut usages = usages.into_values().collect::<Vec<_>>();
+    for usage in &mut usages {
+        usage
+            .largest_shares
+            .sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.id.cmp(&b.id)));
+        usage.largest_shares.truncate(top);
+    }
+    usages.sort_by(|a, b| {
+        b.stored_bytes
+            .cmp(&a.stored_bytes)
+            .then_with(|| a.creator_email.cmp(&b.creator_email))
+    });
+
+    let format_largest = |usage: &Usage| {
+        usage
+            .largest_shares
+            .iter()
+            .map(|share| format!("{} ({})", share.id, ByteSize(share.size)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match output {
+        OutputFormat::Table => {
+            let table = usages
+                .iter()
+                .map(|usage| {
+                    vec![
+                        usage
+                            .creator_email
+                            .as_deref()
+                            .unwrap_or("(no metadata)")
+                            .cell(),
+                        usage.shares.cell().justify(Justify::Right),
+                        ByteSize(usage.stored_bytes)
+                            .to_string()
+                            .cell()
+                            .justify(Justify::Right),
+                        format_largest(usage).cell(),
+                    ]
+                })
+                .collect::<Vec<_>>()
+                .table()
+                .title(vec![
+                    "CREATOR EMAIL".cell(),
+                    "SHARES".cell(),
+                    "STORED".cell(),
+                    "LARGEST SHARES".cell(),
+                ])
+                .separator(
+                    Separator::builder()
+                        .column(None)
+                        .row(None)
+                        .title(None)
+                        .build(),
+                )
+                .border(Border::builder().build());
+            cli_table::print_stdout(table)?;
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&usages)?),
+        OutputFormat::Csv => {
+            println!("creator_email,shares,stored_bytes,largest_shares");
+            for usage in &usages {
+                let largest = usage
+                    .largest_shares
+                    .iter()
+                    .map(|share| format!("{}:{}", share.id, share.size))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                println!(
+                    "{},{},{},{}",
+                    csv_field(usage.creator_email.as_deref().unwrap_or_default()),
+                    usage.shares,
+                    usage.stored_bytes,
+                    csv_field(&largest),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn revoke(s3_client: &aws_sdk_s3::Client, bucket: &str, emails: &[String]) -> Result<()> {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    s3::update_revocations(s3_client, bucket, |revocations| {
+        for email in emails {
+            revocations.revoke(email.clone(), now);
+        }
+    })
+    .await?;
+    for email in emails {
+        println!("sessions of {email} revoked");
+    }
+    Ok(())
+}
+
+async fn list_tokens(s3_client: &aws_sdk_s3::Client, bucket: &str) -> Result<()> {
+    let mut tokens = s3::list_tokens(s3_client, bucket).await?;
+    tokens.sort_by_key(|token| token.created_at);
+    let table = tokens
+        .into_iter()
+        .map(|token| {
+            Ok(vec![
+                token.id.cell(),
+                token.name.cell(),
+                token.creator_email.cell(),
+                serde_json::to_string(&token.scopes)?.cell(),
+                format_timestamp(token.created_at)?.cell(),
+                format_timestamp(token.expires_at)?.cell(),
+                token
+                    .last_used_at
+                    .map(format_timestamp)
+                    .transpose()?
+                    .unwrap_or_else(|| "never".to_string())
+                    .cell(),
+            ])
+        })
+        .collect::<Result<Vec<_>>>()?
+        .table()
+        .title(vec![
+            "ID".cell(),
+            "NAME".cell(),
+            "CREATOR EMAIL".cell(),
+            "SCOPES".cell(),
+            "CREATED AT".cell(),
+            "EXPIRES AT".cell(),
+            "LAST USED AT".cell(),
+        ])
+        .separator(
+            Separator::builder()
+                .column(None)
+                .row(None)
+                .title(None)
+                .build(),
+        )
+        .border(Border::builder().build());
+    cli_table::print_stdout(table)?;
+    Ok(())
+}
+
+async fn create_token(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    email: String,
+    name: String,
+    scopes: Vec<cli::Scope>,
+    expires_in_days: u32,
+) -> Result<()> {
+    let generated = nanum_core::token::generate()?;
+    let now = OffsetDateTime::now_utc();
+    let token = ApiToken {
+        id: generated.id,
+        name,
+        creator_email: email,
+        secret_hash: generated.secret_hash,
+        scopes: scopes.into_iter().map(Into::into).collect(),
+        created_at: now.unix_timestamp(),
+        expires_at: (now + Duration::days(expires_in_days.into())).unix_timestamp(),
+        last_used_at: None,
+    };
+    s3::upload_token(s3_client, bucket, &token).await?;
+    println!("{}", generated.token);
+    Ok(())
+}
+
+async fn delete_tokens(s3_client: &aws_sdk_s3::Client, bucket: &str, ids: &[String]) -> Result<()> {
+    ids.iter()
+        .map(|id| async move {
+            s3::delete_token(s3_client, bucket, id).await?;
+            println!("{id} deleted");
             Ok(())
         })
         .collect::<FuturesUnordered<_>>()
@@ -63,16 +877,222 @@ async fn delete(s3_client: &aws_sdk_s3::Client, bucket: &str, ids: &[String]) ->
         .await
 }
 
+fn keygen() -> Result<()> {
+    let identity = Identity::generate()?;
+    let now = OffsetDateTime::now_utc().format(&Rfc3339)?;
+    println!("# created: {now}");
+    println!("# public key: {}", identity.to_public());
+    println!("{identity}");
+    Ok(())
+}
+
+async fn decrypt(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    id: &str,
+    identity: &Path,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let metadata = s3::get_metadata(s3_client, bucket, id)
+        .await?
+        .with_context(|| format!("{id} not found"))?;
+    if metadata.recipients.is_empty() {
+        bail!("{id} is encrypted with a passphrase, not to public keys");
+    }
+
+    let identity = std::fs::read_to_string(identity)
+        .with_context(|| format!("failed to read {}", identity.display()))?;
+    let identity = recipient::parse_identity(&identity)?;
+    let file_key = identity
+        .unwrap_any(&metadata.recipients)
+        .with_context(|| format!("private key does not match any recipient of {id}"))?;
+    if let Some(check) = &metadata.key_check {
+        if !key_check::verify(&metadata.salt, &file_key, check) {
+            bail!("key check of {id} does not match. The metadata may be corrupted");
+        }
+    }
+
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(&metadata.salt), &file_key)
+        .expand(&[], &mut key)
+        .map_err(|_| anyhow!("cannot expand file key by hkdf"))?;
+    let cipher = XChaCha20Poly1305::new(&key);
+
+    let filename = cipher
+        .decrypt(
+            GenericArray::from_slice(&metadata.filename_nonce),
+            metadata.filename.as_slice(),
+        )
+        .map_err(|_| anyhow!("failed to decrypt filename"))?;
+    let filename = String::from_utf8_lossy(&filename);
+    let info = metadata
+        .info
+        .as_ref()
+        .map(|info| -> Result<FileInfo> {
+            let info = cipher
+                .decrypt(
+                    GenericArray::from_slice(&info.nonce),
+                    info.ciphertext.as_slice(),
+                )
+                .map_err(|_| anyhow!("failed to decrypt file info"))?;
+            Ok(serde_json::from_slice(&info)?)
+        })
+        .transpose()?;
+    // The true size of a padded file is only known from the file info.
+    let size = match (&metadata.padding, &info) {
+        (None, _) => metadata.size,
+        (
+            Some(_),
+            Some(FileInfo {
+                size: Some(size), ..
+            }),
+        ) => *size,
+        (Some(_), _) => bail!("upload of {id} was not completed"),
+    };
+    // The filename is chosen by the uploader, so never let it point outside of the current
+    // directory.
+    let output = match output {
+        Some(output) => output,
+        None => Path::new(&*filename)
+            .file_name()
+            .map(PathBuf::from)
+            .with_context(|| format!("{filename:?} is not a valid filename"))?,
+    };
+    let mut file = File::options()
+        .write(true)
+        .create_new(true)
+        .open(&output)
+        .with_context(|| format!("failed to create {}", output.display()))?;
+
+    let mut decryptor = DecryptorBE32::from_aead(cipher, GenericArray::from_slice(&metadata.nonce));
+    let seq_count = metadata.chunk_count();
+    let mut hasher = Sha256::new();
+    let mut decrypted_len = 0;
+    // Writes the decrypted bytes, dropping the padding after the file.
+    let mut write = |decrypted: &[u8]| -> Result<()> {
+        let len = size.saturating_sub(decrypted_len).min(decrypted.len());
+        file.write_all(&decrypted[..len])?;
+        hasher.update(&decrypted[..len]);
+        decrypted_len += decrypted.len();
+        Ok(())
+    };
+    for seq in 1..seq_count {
+        let chunk = s3::get_file(s3_client, bucket, id, seq)
+            .await?
+            .with_context(|| format!("chunk {seq} of {id} not found"))?;
+        let decrypted = decryptor
+            .decrypt_next(chunk.as_slice())
+            .map_err(|_| anyhow!("failed to decrypt chunk {seq}"))?;
+        write(&decrypted)?;
+    }
+    let chunk = s3::get_file(s3_client, bucket, id, seq_count)
+        .await?
+        .with_context(|| format!("chunk {seq_count} of {id} not found"))?;
+    let decrypted = decryptor
+        .decrypt_last(chunk.as_slice())
+        .map_err(|_| anyhow!("failed to decrypt chunk {seq_count}"))?;
+    write(&decrypted)?;
+
+    if decrypted_len != metadata.size {
+        bail!(
+            "decrypted bytes does not match expected size. expected: {}, actual: {decrypted_len}",
+            metadata.size
+        );
+    }
+    if let Some(info) = info {
+        if hasher.finalize().as_slice() != info.sha256.as_slice() {
+            bail!(
+                "checksum of {} does not match. The file may be corrupted",
+                output.display()
+            );
+        }
+        if let Some(modified_at) = info.modified_at {
+            file.set_modified(OffsetDateTime::from_unix_timestamp(modified_at)?.into())?;
+        }
+    }
+    println!("{id} decrypted to {}", output.display());
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
+    if let Command::Keygen = args.command {
+        return keygen();
+    }
+    let bucket = args
+        .bucket
+        .context("S3 bucket name is required. Set --bucket or S3_BUCKET_NAME")?;
+
     let aws_config = aws_config::load_from_env().await;
     let s3_client = aws_sdk_s3::Client::new(&aws_config);
 
     match args.command {
-        Command::List => list(&s3_client, &args.bucket).await?,
-        Command::Delete { ids } => delete(&s3_client, &args.bucket, &ids).await?,
+        Command::List {
+            creator,
+            older_than,
+            larger_than,
+            incomplete,
+            sort,
+            output,
+        } => {
+            let filter = ListFilter {
+                creator,
+                older_than,
+                larger_than,
+                incomplete,
+            };
+            list(&s3_client, &bucket, &filter, sort, output).await?
+        }
+        Command::Delete { ids } => delete(&s3_client, &bucket, &ids).await?,
+        Command::Show { id } => show(&s3_client, &bucket, &id).await?,
+        Command::SetExpiry { id, expires_in, .. } => {
+            set_expiry(&s3_client, &bucket, &id, expires_in).await?
+        }
+        Command::Disable { id } => set_disabled(&s3_client, &bucket, &id, true).await?,
+        Command::Enable { id } => set_disabled(&s3_client, &bucket, &id, false).await?,
+        Command::Export { ids, to, region } => {
+            let from = Store::Bucket {
+                client: s3_client.clone(),
+                bucket: bucket.clone(),
+            };
+            let to = Store::parse(&to, client_for_region(&s3_client, region).await);
+            transfer(&from, &to, &ids).await?
+        }
+        Command::Import { ids, from, region } => {
+            let from = Store::parse(&from, client_for_region(&s3_client, region).await);
+            let to = Store::Bucket {
+                client: s3_client.clone(),
+                bucket: bucket.clone(),
+            };
+            let ids = if ids.is_empty() {
+                from.list_ids().await?
+            } else {
+                ids
+            };
+            transfer(&from, &to, &ids).await?
+        }
+        Command::Reindex => reindex(&s3_client, &bucket).await?,
+        Command::Usage { top, output } => usage(&s3_client, &bucket, top, output).await?,
+        Command::Gc { delete, grace } => gc(&s3_client, &bucket, grace, delete).await?,
+        Command::Revoke { emails } => revoke(&s3_client, &bucket, &emails).await?,
+        Command::Token(TokenCommand::List) => list_tokens(&s3_client, &bucket).await?,
+        Command::Token(TokenCommand::Create {
+            email,
+            name,
+            scopes,
+            expires_in_days,
+        }) => create_token(&s3_client, &bucket, email, name, scopes, expires_in_days).await?,
+        Command::Token(TokenCommand::Delete { ids }) => {
+            delete_tokens(&s3_client, &bucket, &ids).await?
+        }
+        Command::Keygen => unreachable!(),
+        Command::Decrypt {
+            id,
+            identity,
+            output,
+        } => decrypt(&s3_client, &bucket, &id, &identity, output).await?,
     }
 
     Ok(())
@@ -1,9 +1,12 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use aws_sdk_s3::{
-    error::SdkError, operation::get_object::GetObjectError, primitives::ByteStream, Client,
+    error::SdkError, operation::get_object::GetObjectError, primitives::ByteStream, types::Object,
+    Client,
 };
 use futures_util::{TryFutureExt, TryStreamExt};
-use nanum_core::types::Metadata;
+use http::{header, HeaderName, HeaderValue, StatusCode};
+use nanum_core::types::{ApiToken, Metadata, Revocations, ShareIndex};
+use serde::{de::DeserializeOwned, Serialize};
 
 pub async fn list_metadatas(client: &Client, bucket: &str) -> Result<Vec<(String, Metadata)>> {
     client
@@ -42,6 +45,29 @@ pub async fn list_metadatas(client: &Client, bucket: &str) -> Result<Vec<(String
         .await
 }
 
+/// Lists every object under `prefix`, with their sizes.
+pub async fn list_objects(client: &Client, bucket: &str, prefix: &str) -> Result<Vec<Object>> {
+    client
+        .list_objects_v2()
+        .bucket(bucket)
+        .prefix(prefix)
+        .into_paginator()
+        .send()
+        .err_into::<anyhow::Error>()
+        .map_ok(|output| {
+            futures_util::stream::iter(
+                output
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Result::<_, anyhow::Error>::Ok),
+            )
+        })
+        .try_flatten()
+        .try_collect()
+        .await
+}
+
 async fn get_object(s3_client: &Client, bucket: &str, key: &str) -> Result<Option<ByteStream>> {
     let resp = s3_client.get_object().bucket(bucket).key(key).send().await;
     match resp {
@@ -60,6 +86,47 @@ async fn get_object(s3_client: &Client, bucket: &str, key: &str) -> Result<Optio
     }
 }
 
+pub async fn get_bytes(client: &Client, bucket: &str, key: &str) -> Result<Option<Vec<u8>>> {
+    if let Some(resp) = get_object(client, bucket, key).await? {
+        Ok(Some(resp.collect().await?.into_bytes().to_vec()))
+    } else {
+        Ok(None)
+    }
+}
+
+pub async fn put_bytes(client: &Client, bucket: &str, key: &str, body: Vec<u8>) -> Result<()> {
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(body.into())
+        .send()
+        .await?;
+    Ok(())
+}
+
+pub async fn get_metadata(client: &Client, bucket: &str, id: &str) -> Result<Option<Metadata>> {
+    if let Some(resp) = get_object(client, bucket, &format!("metadata/{id}.json")).await? {
+        let body = resp.collect().await?.into_bytes();
+        Ok(Some(serde_json::from_slice(&body)?))
+    } else {
+        Ok(None)
+    }
+}
+
+pub async fn get_file(
+    client: &Client,
+    bucket: &str,
+    id: &str,
+    seq: usize,
+) -> Result<Option<Vec<u8>>> {
+    if let Some(resp) = get_object(client, bucket, &format!("file/{id}.{seq}")).await? {
+        Ok(Some(resp.collect().await?.into_bytes().to_vec()))
+    } else {
+        Ok(None)
+    }
+}
+
 pub async fn delete_metadata(client: &Client, bucket: &str, id: &str) -> Result<()> {
     client
         .delete_object()
@@ -70,6 +137,16 @@ pub async fn delete_metadata(client: &Client, bucket: &str, id: &str) -> Result<
     Ok(())
 }
 
+pub async fn delete_object(client: &Client, bucket: &str, key: &str) -> Result<()> {
+    client
+        .delete_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await?;
+    Ok(())
+}
+
 pub async fn delete_file(client: &Client, bucket: &str, id: &str) -> Result<()> {
     client
         .list_objects_v2()
@@ -101,3 +178,229 @@ pub async fn delete_file(client: &Client, bucket: &str, id: &str) -> Result<()>
         .try_collect::<()>()
         .await
 }
+
+async fn get_json_with_etag<T: DeserializeOwned>(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+) -> Result<Option<(T, String)>> {
+    let resp = client.get_object().bucket(bucket).key(key).send().await;
+    match resp {
+        Ok(resp) => {
+            let etag = resp
+                .e_tag()
+                .ok_or_else(|| anyhow!("S3 did not return ETag of {key}"))?
+                .to_string();
+            let body = resp.body.collect().await?.into_bytes();
+            Ok(Some((serde_json::from_slice(&body)?, etag)))
+        }
+        Err(SdkError::ServiceError(error))
+            if matches!(error.err(), GetObjectError::NoSuchKey(_)) =>
+        {
+            Ok(None)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+async fn get_index_with_etag(
+    client: &Client,
+    bucket: &str,
+) -> Result<Option<(ShareIndex, String)>> {
+    get_json_with_etag(client, bucket, "index.json").await
+}
+
+/// Puts `value` to `key` only if `precondition` holds. Returns `false` if it does not.
+async fn put_json_with_precondition<T: Serialize>(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    value: &T,
+    precondition: (HeaderName, HeaderValue),
+) -> Result<bool> {
+    let resp = client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(serde_json::to_vec(value)?.into())
+        .customize()
+        .await?
+        .mutate_request(move |req| {
+            req.headers_mut().insert(precondition.0, precondition.1);
+        })
+        .send()
+        .await;
+    match resp {
+        Ok(_) => Ok(true),
+        // `If-Match` on a deleted object fails with 404
+        Err(SdkError::ServiceError(error))
+            if error.raw().http().status() == StatusCode::PRECONDITION_FAILED
+                || error.raw().http().status() == StatusCode::CONFLICT
+                || error.raw().http().status() == StatusCode::NOT_FOUND =>
+        {
+            Ok(false)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Puts `value` to `key` only if the object is still `etag`. Returns `false` if it was
+/// modified.
+async fn put_json_if_match<T: Serialize>(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    value: &T,
+    etag: &str,
+) -> Result<bool> {
+    let precondition = (header::IF_MATCH, HeaderValue::from_str(etag)?);
+    put_json_with_precondition(client, bucket, key, value, precondition).await
+}
+
+pub async fn upload_index(client: &Client, bucket: &str, index: &ShareIndex) -> Result<()> {
+    client
+        .put_object()
+        .bucket(bucket)
+        .key("index.json")
+        .body(serde_json::to_vec(index)?.into())
+        .send()
+        .await?;
+    Ok(())
+}
+
+/// The backend writes the index on every upload, so a lost conditional write is retried.
+const MAX_INDEX_UPDATE_ATTEMPTS: usize = 5;
+
+/// Applies `update` to the index with a conditional write. Does nothing if there is no index.
+pub async fn update_index(
+    client: &Client,
+    bucket: &str,
+    update: impl Fn(&mut ShareIndex),
+) -> Result<()> {
+    for _ in 0..MAX_INDEX_UPDATE_ATTEMPTS {
+        let (mut index, etag) = match get_index_with_etag(client, bucket).await? {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        update(&mut index);
+
+        if put_json_if_match(client, bucket, "index.json", &index, &etag).await? {
+            return Ok(());
+        }
+    }
+    bail!("share index modified concurrently")
+}
+
+/// Metadata is also updated by the backend, e.g. when an upload completes.
+const MAX_METADATA_UPDATE_ATTEMPTS: usize = 5;
+
+/// Applies `update` to the metadata of `id` with a conditional write, and returns the updated
+/// metadata. Returns `None` if there is no such share.
+pub async fn update_metadata(
+    client: &Client,
+    bucket: &str,
+    id: &str,
+    update: impl Fn(&mut Metadata),
+) -> Result<Option<Metadata>> {
+    let key = format!("metadata/{id}.json");
+    for _ in 0..MAX_METADATA_UPDATE_ATTEMPTS {
+        let (mut metadata, etag) =
+            match get_json_with_etag::<Metadata>(client, bucket, &key).await? {
+                Some(metadata) => metadata,
+                None => return Ok(None),
+            };
+
+        update(&mut metadata);
+
+        if put_json_if_match(client, bucket, &key, &metadata, &etag).await? {
+            return Ok(Some(metadata));
+        }
+    }
+    bail!("metadata of {id} modified concurrently")
+}
+
+/// Revocations may be written by other administrators at the same time.
+const MAX_REVOCATIONS_UPDATE_ATTEMPTS: usize = 5;
+
+/// Applies `update` to the revocations with a conditional write, so that no concurrent revocation
+/// is lost.
+pub async fn update_revocations(
+    client: &Client,
+    bucket: &str,
+    update: impl Fn(&mut Revocations),
+) -> Result<()> {
+    let key = "revocations.json";
+    for _ in 0..MAX_REVOCATIONS_UPDATE_ATTEMPTS {
+        let (mut revocations, precondition) =
+            match get_json_with_etag::<Revocations>(client, bucket, key).await? {
+                Some((revocations, etag)) => (
+                    revocations,
+                    (header::IF_MATCH, HeaderValue::from_str(&etag)?),
+                ),
+                None => (
+                    Revocations::default(),
+                    (header::IF_NONE_MATCH, HeaderValue::from_static("*")),
+                ),
+            };
+
+        update(&mut revocations);
+
+        if put_json_with_precondition(client, bucket, key, &revocations, precondition).await? {
+            return Ok(());
+        }
+    }
+    bail!("revocations modified concurrently")
+}
+
+pub async fn list_tokens(client: &Client, bucket: &str) -> Result<Vec<ApiToken>> {
+    client
+        .list_objects_v2()
+        .bucket(bucket)
+        .prefix("token/")
+        .into_paginator()
+        .send()
+        .err_into::<anyhow::Error>()
+        .map_ok(|output| {
+            futures_util::stream::iter(
+                output
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Result::<_, anyhow::Error>::Ok),
+            )
+        })
+        .try_flatten()
+        .try_filter_map(|content| async move {
+            if let Some(key) = content.key() {
+                if let Some(resp) = get_object(client, bucket, key).await? {
+                    let body = resp.collect().await?.into_bytes();
+                    return Ok(serde_json::from_slice::<ApiToken>(&body).ok());
+                }
+            }
+            Ok(None)
+        })
+        .try_collect()
+        .await
+}
+
+pub async fn upload_token(client: &Client, bucket: &str, token: &ApiToken) -> Result<()> {
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(format!("token/{}.json", token.id))
+        .body(serde_json::to_vec(token)?.into())
+        .send()
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_token(client: &Client, bucket: &str, id: &str) -> Result<()> {
+    client
+        .delete_object()
+        .bucket(bucket)
+        .key(format!("token/{id}.json"))
+        .send()
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::s3;
+
+/// Where shares are copied from or to by `export` and `import`. A directory has the same layout
+/// as a bucket, i.e. `metadata/{id}.json` and `file/{id}.{seq}`.
+pub enum Store {
+    Bucket {
+        client: aws_sdk_s3::Client,
+        bucket: String,
+    },
+    Dir(PathBuf),
+}
+
+impl Store {
+    /// Parses `s3://<bucket>` or a directory path.
+    pub fn parse(location: &str, client: aws_sdk_s3::Client) -> Self {
+        match location.strip_prefix("s3://") {
+            Some(bucket) => Self::Bucket {
+                client,
+                bucket: bucket.trim_end_matches('/').to_string(),
+            },
+            None => Self::Dir(PathBuf::from(location)),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self {
+            Self::Bucket { client, bucket } => s3::get_bytes(client, bucket, key).await,
+            Self::Dir(dir) => {
+                let path = dir.join(key);
+                match std::fs::read(&path) {
+                    Ok(body) => Ok(Some(body)),
+                    Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    Err(error) => {
+                        Err(error).with_context(|| format!("failed to read {}", path.display()))
+                    }
+                }
+            }
+        }
+    }
+
+    pub async fn put(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        match self {
+            Self::Bucket { client, bucket } => s3::put_bytes(client, bucket, key, body).await,
+            Self::Dir(dir) => {
+                let path = dir.join(key);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .with_context(|| format!("failed to create {}", parent.display()))?;
+                }
+                std::fs::write(&path, body)
+                    .with_context(|| format!("failed to write {}", path.display()))
+            }
+        }
+    }
+
+    /// IDs of every share with metadata.
+    pub async fn list_ids(&self) -> Result<Vec<String>> {
+        let names = match self {
+            Self::Bucket { client, bucket } => s3::list_objects(client, bucket, "metadata/")
+                .await?
+                .into_iter()
+                .filter_map(|object| Some(object.key()?.strip_prefix("metadata/")?.to_string()))
+                .collect::<Vec<_>>(),
+            Self::Dir(dir) => {
+                let path = dir.join("metadata");
+                std::fs::read_dir(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?
+                    .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+                    .collect::<Result<Vec<_>>>()?
+            }
+        };
+        Ok(names
+            .into_iter()
+            .filter_map(|name| name.strip_suffix(".json").map(str::to_string))
+            .collect())
+    }
+
+    /// Sizes of the stored chunks of a share, by sequence number.
+    pub async fn chunk_sizes(&self, id: &str) -> Result<BTreeMap<usize, u64>> {
+        let chunks = match self {
+            Self::Bucket { client, bucket } => {
+                s3::list_objects(client, bucket, &format!("file/{id}."))
+                    .await?
+                    .into_iter()
+                    .filter_map(|object| {
+                        let name = object.key()?.strip_prefix("file/")?.to_string();
+                        Some((name, object.size().max(0) as u64))
+                    })
+                    .collect::<Vec<_>>()
+            }
+            Self::Dir(dir) => {
+                let path = dir.join("file");
+                match std::fs::read_dir(&path) {
+                    Ok(entries) => entries
+                        .map(|entry| {
+                            let entry = entry?;
+                            Ok((
+                                entry.file_name().to_string_lossy().into_owned(),
+                                entry.metadata()?.len(),
+                            ))
+                        })
+                        .collect::<Result<Vec<_>>>()?,
+                    Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                    Err(error) => {
+                        return Err(error)
+                            .with_context(|| format!("failed to read {}", path.display()))
+                    }
+                }
+            }
+        };
+        Ok(chunks
+            .into_iter()
+            .filter_map(|(name, size)| {
+                let (chunk_id, seq) = name.rsplit_once('.')?;
+                (chunk_id == id).then_some((seq.parse().ok()?, size))
+            })
+            .collect())
+    }
+}
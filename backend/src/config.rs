@@ -35,6 +35,68 @@ fn default_random_uri_length() -> usize {
     8
 }
 
+fn deserialize_random_uri_length<'de, D>(d: D) -> Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let length = usize::deserialize(d)?;
+    if !(1..=nanum_core::id::MAX_ID_LENGTH).contains(&length) {
+        return Err(serde::de::Error::custom(format!(
+            "random URI length must be between 1 and {}",
+            nanum_core::id::MAX_ID_LENGTH
+        )));
+    }
+    Ok(length)
+}
+
+fn default_random_uri_alphabet() -> Vec<char> {
+    "abcdefghijklmnopqrstuvwxyz0123456789".chars().collect()
+}
+
+fn deserialize_random_uri_alphabet<'de, D>(d: D) -> Result<Vec<char>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(d)?;
+    let mut alphabet = s.chars().collect::<Vec<_>>();
+    alphabet.sort_unstable();
+    alphabet.dedup();
+    if alphabet.len() < 2 {
+        return Err(serde::de::Error::custom(
+            "random URI alphabet must have at least 2 characters",
+        ));
+    }
+    if !alphabet
+        .iter()
+        .all(|c| nanum_core::id::is_valid_id_char(*c))
+    {
+        return Err(serde::de::Error::custom(
+            "random URI alphabet may only contain letters, digits, '-' and '_'",
+        ));
+    }
+    Ok(alphabet)
+}
+
+fn default_min_passphrase_entropy() -> f64 {
+    50.
+}
+
+fn default_not_found_burst() -> u32 {
+    20
+}
+
+fn default_not_found_per_minute() -> f64 {
+    10.
+}
+
+fn default_download_burst() -> u32 {
+    1000
+}
+
+fn default_download_per_minute() -> f64 {
+    300.
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     #[serde(default = "default_listen_addr")]
@@ -54,8 +116,51 @@ pub struct Config {
 
     pub s3_bucket_name: String,
 
-    #[serde(default = "default_random_uri_length")]
+    #[serde(
+        default = "default_random_uri_length",
+        deserialize_with = "deserialize_random_uri_length"
+    )]
     pub random_uri_length: usize,
+
+    #[serde(
+        default = "default_random_uri_alphabet",
+        deserialize_with = "deserialize_random_uri_alphabet"
+    )]
+    pub random_uri_alphabet: Vec<char>,
+
+    /// Enforced by the web UI only, since passphrases never reach the server.
+    #[serde(default = "default_min_passphrase_entropy")]
+    pub min_passphrase_entropy: f64,
+
+    /// Number of reverse proxies in front of the server, which append the address of their client
+    /// to `X-Forwarded-For`. If 0, the header is ignored and the peer address is used.
+    #[serde(default)]
+    pub trusted_proxy_count: usize,
+
+    /// API requests answered with 404 a client can make in a row, which slows down probing of
+    /// share IDs. 0 disables the limit.
+    #[serde(default = "default_not_found_burst")]
+    pub rate_limit_not_found_burst: u32,
+    #[serde(default = "default_not_found_per_minute")]
+    pub rate_limit_not_found_per_minute: f64,
+
+    /// Chunks a client can download in a row. 0 disables the limit. Chunks are 10 MiB, so by
+    /// default a client can download 10 GiB at once and 50 MiB/s after that.
+    #[serde(default = "default_download_burst")]
+    pub rate_limit_download_burst: u32,
+    #[serde(default = "default_download_per_minute")]
+    pub rate_limit_download_per_minute: f64,
+
+    /// SQLite database to keep share records in. If not set, they are kept in the bucket.
+    #[cfg(feature = "sqlite")]
+    pub database_path: Option<std::path::PathBuf>,
+}
+
+impl Config {
+    /// Entropy of randomly generated share IDs in bits.
+    pub fn random_uri_entropy(&self) -> f64 {
+        self.random_uri_length as f64 * (self.random_uri_alphabet.len() as f64).log2()
+    }
 }
 
 pub static CONFIG: Lazy<Config> = Lazy::new(|| {
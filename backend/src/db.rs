@@ -0,0 +1,190 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use nanum_core::types::{IndexEntry, ShareSummary};
+use rusqlite::{params, Connection};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS shares (
+    id TEXT PRIMARY KEY NOT NULL,
+    creator_email TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    created_at INTEGER,
+    expires_at INTEGER,
+    completed INTEGER NOT NULL,
+    downloads INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS shares_creator_email ON shares (creator_email);
+";
+
+fn upsert_share(conn: &Connection, id: &str, entry: &IndexEntry) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT INTO shares (id, creator_email, size, created_at, expires_at, completed)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT (id) DO UPDATE SET
+             creator_email = excluded.creator_email,
+             size = excluded.size,
+             created_at = excluded.created_at,
+             expires_at = excluded.expires_at,
+             completed = excluded.completed",
+        params![
+            id,
+            entry.creator_email,
+            entry.size as i64,
+            entry.created_at,
+            entry.expires_at,
+            entry.completed,
+        ],
+    )
+}
+
+/// SQLite database of share records and download counters. Queries run on the blocking thread
+/// pool.
+///
+/// Session revocations and API tokens stay in the bucket, since `nanum-admin` manages them there
+/// too. They are updated with conditional writes, so concurrent updates are not lost.
+#[derive(Clone)]
+pub struct Database {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open database {}", path.display()))?;
+        conn.execute_batch(SCHEMA)
+            .context("failed to create database schema")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let conn = self.conn.clone();
+        let result = tokio::task::spawn_blocking(move || f(&mut conn.lock().unwrap())).await?;
+        Ok(result?)
+    }
+
+    /// Inserts or replaces the record of a share, keeping its download count.
+    pub async fn put_share(&self, id: String, entry: IndexEntry) -> Result<()> {
+        self.run(move |conn| upsert_share(conn, &id, &entry))
+            .await?;
+        Ok(())
+    }
+
+    /// Replaces every record with `entries` in a single transaction, keeping download counts of
+    /// shares which are still there.
+    pub async fn sync_shares(&self, entries: BTreeMap<String, IndexEntry>) -> Result<()> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            for (id, entry) in &entries {
+                upsert_share(&tx, id, entry)?;
+            }
+            let ids = tx
+                .prepare("SELECT id FROM shares")?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for id in ids {
+                if !entries.contains_key(&id) {
+                    tx.execute("DELETE FROM shares WHERE id = ?1", [id])?;
+                }
+            }
+            tx.commit()
+        })
+        .await
+    }
+
+    pub async fn remove_share(&self, id: String) -> Result<()> {
+        self.run(move |conn| conn.execute("DELETE FROM shares WHERE id = ?1", [id]))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn list_shares(&self, creator_email: String) -> Result<Vec<ShareSummary>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, size, created_at, expires_at, downloads FROM shares
+                 WHERE creator_email = ?1",
+            )?;
+            let shares = stmt
+                .query_map([creator_email], |row| {
+                    Ok(ShareSummary {
+                        id: row.get(0)?,
+                        size: row.get::<_, i64>(1)? as usize,
+                        created_at: row.get(2)?,
+                        expires_at: row.get(3)?,
+                        downloads: Some(row.get::<_, i64>(4)? as u64),
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(shares)
+        })
+        .await
+    }
+
+    pub async fn count_download(&self, id: String) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE shares SET downloads = downloads + 1 WHERE id = ?1",
+                [id],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(creator_email: &str) -> IndexEntry {
+        IndexEntry {
+            creator_email: creator_email.to_string(),
+            size: 10,
+            block_size: 1024,
+            padding: None,
+            created_at: Some(0),
+            completed_at: Some(0),
+            expires_at: None,
+            completed: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_keeps_download_counts_and_drops_missing_shares() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        db.put_share("kept".to_string(), entry("a@example.com"))
+            .await
+            .unwrap();
+        db.put_share("removed".to_string(), entry("a@example.com"))
+            .await
+            .unwrap();
+        db.count_download("kept".to_string()).await.unwrap();
+
+        let mut expiring = entry("a@example.com");
+        expiring.expires_at = Some(100);
+        db.sync_shares(BTreeMap::from([
+            ("kept".to_string(), expiring),
+            ("added".to_string(), entry("a@example.com")),
+        ]))
+        .await
+        .unwrap();
+
+        let mut shares = db.list_shares("a@example.com".to_string()).await.unwrap();
+        shares.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(shares.len(), 2);
+        assert_eq!(shares[0].id, "added");
+        assert_eq!(shares[0].downloads, Some(0));
+        assert_eq!(shares[1].id, "kept");
+        assert_eq!(shares[1].downloads, Some(1));
+        assert_eq!(shares[1].expires_at, Some(100));
+    }
+}
@@ -1,27 +1,61 @@
 use aws_sdk_s3::primitives::ByteStream;
-use axum::{
-    body::{Bytes, StreamBody},
-    extract::{Path, State},
-    http::StatusCode,
-    routing, Json, Router,
+use axum::{body::StreamBody, extract::State, routing, Router};
+use nanum_core::{
+    error::ApiErrorCode,
+    key_check, key_slot,
+    recipient::{FILE_KEY_LENGTH, MAX_RECIPIENTS},
+    types::{
+        ApiToken, ApiTokenCreationReq, ApiTokenCreationResp, ApiTokenInfo, ClientConfig,
+        EncryptedFileInfo, IndexEntry, KeySlot, Metadata, MetadataCreationReq, ShareInfo,
+        ShareSummary, TokenScope,
+    },
 };
-use nanum_core::types::{Metadata, MetadataCreationReq};
+use rand::{distributions::Slice, rngs::OsRng, Rng};
 use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
 
 use crate::{config::CONFIG, s3};
 
-use super::{auth::User, AppState};
+use super::{
+    auth::User,
+    error::{ErrorResp, Json, Path, RawBody},
+    AppState,
+};
 
 pub fn create_router() -> Router<AppState> {
     Router::new()
         .route("/health", routing::get(get_health))
         .route("/user", routing::get(get_user))
+        .route("/config", routing::get(get_config))
         .route(
             "/metadata/:id",
             routing::get(get_metadata).post(post_metadata),
         )
         .route("/metadata", routing::post(post_metadata_with_random_id))
+        .route(
+            "/metadata/:id/complete",
+            routing::post(post_metadata_complete),
+        )
+        .route("/metadata/:id/slots", routing::post(post_key_slot))
+        .route(
+            "/metadata/:id/slots/:name",
+            routing::delete(delete_key_slot),
+        )
         .route("/file/:id/:seq", routing::get(get_file).post(post_file))
+        .route("/shares", routing::get(get_shares))
+        .route("/shares/:id", routing::delete(delete_share))
+        .route("/tokens", routing::get(get_tokens).post(post_token))
+        .route("/tokens/:id", routing::delete(delete_token))
+        .fallback(fallback)
+}
+
+async fn fallback() -> ErrorResp {
+    ErrorResp::new(ApiErrorCode::NotFound, "no such API")
+}
+
+fn validate_id(id: &str) -> Result<(), ErrorResp> {
+    nanum_core::id::validate(id)
+        .map_err(|error| ErrorResp::new(ApiErrorCode::InvalidId, error.message()))
 }
 
 async fn get_health() -> &'static str {
@@ -32,21 +66,42 @@ async fn get_user(user: User) -> Json<User> {
     Json(user)
 }
 
+async fn get_config() -> Json<ClientConfig> {
+    Json(ClientConfig {
+        min_passphrase_entropy: CONFIG.min_passphrase_entropy,
+    })
+}
+
 async fn get_metadata(
     Path(id): Path<String>,
+    user: Option<User>,
     State(state): State<AppState>,
-) -> Result<Json<Metadata>, (StatusCode, &'static str)> {
+) -> Result<Json<ShareInfo>, ErrorResp> {
+    if nanum_core::id::validate(&id).is_err() {
+        return Err(ErrorResp::new(ApiErrorCode::NotFound, "share not found"));
+    }
+
     let metadata = s3::get_metadata(&state.s3_client, &id)
         .await
         .map_err(|error| {
             tracing::error!(%error, "failed to get metadata from S3");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "failed to get metadata from S3",
-            )
+            ErrorResp::new(ApiErrorCode::Internal, "failed to get metadata from S3")
         })?
-        .ok_or((StatusCode::NOT_FOUND, ""))?;
-    Ok(Json(metadata))
+        .ok_or_else(|| ErrorResp::new(ApiErrorCode::NotFound, "share not found"))?;
+    check_downloadable(&metadata)?;
+    let is_owner = user.is_some_and(|user| user.primary_email == metadata.creator_email);
+    Ok(Json(metadata.into_share_info(is_owner)))
+}
+
+/// Fails if the share expired or was disabled by an administrator.
+fn check_downloadable(metadata: &Metadata) -> Result<(), ErrorResp> {
+    if metadata.is_expired(OffsetDateTime::now_utc().unix_timestamp()) {
+        return Err(ErrorResp::new(ApiErrorCode::NotFound, "share not found"));
+    }
+    if metadata.disabled_at.is_some() {
+        return Err(ErrorResp::new(ApiErrorCode::Disabled, "share is disabled"));
+    }
+    Ok(())
 }
 
 #[derive(Deserialize)]
@@ -55,39 +110,125 @@ struct PostMetadataReq {
     pub req: MetadataCreationReq,
 }
 
+fn validate_key_slot(slot: &KeySlot) -> Result<(), ErrorResp> {
+    let name = slot.name.trim();
+    if name.is_empty() || name.len() > key_slot::MAX_NAME_LENGTH || name != slot.name {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "invalid key slot name",
+        ));
+    }
+    // 16 bytes of Poly1305 tag
+    if slot.salt.len() != key_slot::SALT_LENGTH
+        || slot.nonce.len() != key_slot::NONCE_LENGTH
+        || slot.wrapped_key.len() != FILE_KEY_LENGTH + 16
+    {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "malformed key slot",
+        ));
+    }
+    Ok(())
+}
+
+fn validate_key_slots(slots: &[KeySlot]) -> Result<(), ErrorResp> {
+    if slots.len() > key_slot::MAX_KEY_SLOTS {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "too many key slots",
+        ));
+    }
+    for (i, slot) in slots.iter().enumerate() {
+        validate_key_slot(slot)?;
+        if slots[..i].iter().any(|other| other.name == slot.name) {
+            return Err(ErrorResp::new(
+                ApiErrorCode::BadRequest,
+                "duplicated key slot name",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Wrapped keys can't be verified without the passphrases or the recipients' private keys, so
+/// only their shape is checked.
+fn validate_keys(req: &MetadataCreationReq) -> Result<(), ErrorResp> {
+    if !req.recipients.is_empty() && !req.key_slots.is_empty() {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "share cannot have both recipients and key slots",
+        ));
+    }
+    validate_key_slots(&req.key_slots)?;
+    if req
+        .key_check
+        .as_ref()
+        .is_some_and(|check| check.len() != key_check::KEY_CHECK_LENGTH)
+    {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "malformed key check",
+        ));
+    }
+    if req.recipients.len() > MAX_RECIPIENTS {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "too many recipients",
+        ));
+    }
+    // 16 bytes of Poly1305 tag
+    if req.recipients.iter().any(|recipient| {
+        recipient.ephemeral_public.len() != 32
+            || recipient.wrapped_key.len() != FILE_KEY_LENGTH + 16
+    }) {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "malformed recipient",
+        ));
+    }
+    Ok(())
+}
+
 #[derive(Serialize)]
 struct PostMetadataResp {
     pub id: String,
 }
 
-async fn upload_metadata(
-    s3_client: &aws_sdk_s3::Client,
-    id: &str,
-    user: User,
-    req: MetadataCreationReq,
-) -> Result<(), (StatusCode, &'static str)> {
-    let existing_metadata = s3::get_metadata(s3_client, id).await.map_err(|error| {
-        tracing::error!(%error, "failed to get metadata from S3");
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "failed to get metadata from S3",
-        )
-    })?;
-    if existing_metadata.is_some() {
-        return Err((StatusCode::CONFLICT, ""));
+/// Share IDs are random, so a collision is retried with a new ID.
+const MAX_RANDOM_ID_ATTEMPTS: usize = 5;
+
+fn generate_random_id() -> String {
+    let alphabet = Slice::new(&CONFIG.random_uri_alphabet).unwrap();
+    OsRng
+        .sample_iter(alphabet)
+        .take(CONFIG.random_uri_length)
+        .collect()
+}
+
+/// Puts or removes the record of a share. Records which fail to update are rebuilt later, see
+/// [`crate::records::ShareRecords`].
+async fn sync_record(state: &AppState, id: &str, entry: Option<IndexEntry>) {
+    match entry {
+        Some(entry) => state.records.put(id, entry).await,
+        None => state.records.remove(id).await,
     }
+}
 
-    let metadata = req.into_metadata(user.primary_email);
-    s3::upload_metadata(s3_client, id, &metadata)
+async fn create_metadata(
+    state: &AppState,
+    id: &str,
+    metadata: &Metadata,
+) -> Result<bool, ErrorResp> {
+    let created = s3::create_metadata(&state.s3_client, id, metadata)
         .await
         .map_err(|error| {
             tracing::error!(%error, "failed to upload metadata to S3");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "failed to upload metadata to S3",
-            )
+            ErrorResp::new(ApiErrorCode::Internal, "failed to upload metadata to S3")
         })?;
-    Ok(())
+    if created {
+        sync_record(state, id, Some(metadata.index_entry())).await;
+    }
+    Ok(created)
 }
 
 async fn post_metadata(
@@ -95,8 +236,18 @@ async fn post_metadata(
     user: User,
     State(state): State<AppState>,
     Json(req): Json<PostMetadataReq>,
-) -> Result<Json<PostMetadataResp>, (StatusCode, &'static str)> {
-    upload_metadata(&state.s3_client, &id, user, req.req).await?;
+) -> Result<Json<PostMetadataResp>, ErrorResp> {
+    user.require_scope(TokenScope::Upload)?;
+    validate_id(&id)?;
+    validate_keys(&req.req)?;
+
+    let metadata = req.req.into_metadata(
+        user.primary_email,
+        OffsetDateTime::now_utc().unix_timestamp(),
+    );
+    if !create_metadata(&state, &id, &metadata).await? {
+        return Err(ErrorResp::new(ApiErrorCode::IdTaken, "ID already taken"));
+    }
     Ok(Json(PostMetadataResp { id }))
 }
 
@@ -104,59 +255,395 @@ async fn post_metadata_with_random_id(
     user: User,
     State(state): State<AppState>,
     Json(req): Json<PostMetadataReq>,
-) -> Result<Json<PostMetadataResp>, (StatusCode, &'static str)> {
-    let id = random_string::generate(
-        CONFIG.random_uri_length,
-        "abcedfghijklmnopqrstuvwxyz0123456789",
+) -> Result<Json<PostMetadataResp>, ErrorResp> {
+    user.require_scope(TokenScope::Upload)?;
+    validate_keys(&req.req)?;
+
+    let metadata = req.req.into_metadata(
+        user.primary_email,
+        OffsetDateTime::now_utc().unix_timestamp(),
     );
-    upload_metadata(&state.s3_client, &id, user, req.req).await?;
-    Ok(Json(PostMetadataResp { id }))
+    for _ in 0..MAX_RANDOM_ID_ATTEMPTS {
+        let id = generate_random_id();
+        if nanum_core::id::validate(&id).is_err() {
+            continue;
+        }
+        if create_metadata(&state, &id, &metadata).await? {
+            return Ok(Json(PostMetadataResp { id }));
+        }
+        tracing::warn!(%id, "random ID collided with an existing share");
+    }
+
+    tracing::error!("failed to generate an unused random ID");
+    Err(ErrorResp::new(
+        ApiErrorCode::Internal,
+        "failed to generate an unused random ID",
+    ))
+}
+
+/// Metadata may be updated concurrently by the owner, so a lost conditional write is retried.
+const MAX_METADATA_UPDATE_ATTEMPTS: usize = 3;
+
+/// Applies `update` to metadata of a share owned by `user`.
+async fn update_owned_metadata(
+    state: &AppState,
+    id: &str,
+    user: &User,
+    update: impl Fn(&mut Metadata) -> Result<(), ErrorResp>,
+) -> Result<(), ErrorResp> {
+    if nanum_core::id::validate(id).is_err() {
+        return Err(ErrorResp::new(ApiErrorCode::NotFound, "share not found"));
+    }
+
+    for _ in 0..MAX_METADATA_UPDATE_ATTEMPTS {
+        let (mut metadata, etag) = s3::get_metadata_with_etag(&state.s3_client, id)
+            .await
+            .map_err(|error| {
+                tracing::error!(%error, "failed to get metadata from S3");
+                ErrorResp::new(ApiErrorCode::Internal, "failed to get metadata from S3")
+            })?
+            .ok_or_else(|| ErrorResp::new(ApiErrorCode::NotFound, "share not found"))?;
+        if metadata.creator_email != user.primary_email {
+            return Err(ErrorResp::new(
+                ApiErrorCode::Forbidden,
+                "only the owner can modify the share",
+            ));
+        }
+
+        let entry = metadata.index_entry();
+        update(&mut metadata)?;
+
+        let updated = s3::update_metadata(&state.s3_client, id, &metadata, &etag)
+            .await
+            .map_err(|error| {
+                tracing::error!(%error, "failed to update metadata in S3");
+                ErrorResp::new(ApiErrorCode::Internal, "failed to update metadata in S3")
+            })?;
+        if updated {
+            if metadata.index_entry() != entry {
+                sync_record(state, id, Some(metadata.index_entry())).await;
+            }
+            return Ok(());
+        }
+        tracing::warn!(%id, "metadata modified concurrently");
+    }
+
+    Err(ErrorResp::new(
+        ApiErrorCode::Internal,
+        "metadata modified concurrently",
+    ))
+}
+
+/// Encrypted file info is small JSON, so anything larger is rejected.
+const MAX_FILE_INFO_LENGTH: usize = 8192;
+
+/// Stores encrypted file info once the uploader has hashed and uploaded every chunk.
+async fn post_metadata_complete(
+    Path(id): Path<String>,
+    user: User,
+    State(state): State<AppState>,
+    Json(info): Json<EncryptedFileInfo>,
+) -> Result<(), ErrorResp> {
+    user.require_scope(TokenScope::Upload)?;
+    if info.nonce.len() != 24 || info.ciphertext.len() > MAX_FILE_INFO_LENGTH {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "malformed file info",
+        ));
+    }
+
+    update_owned_metadata(&state, &id, &user, |metadata| {
+        if metadata.info.is_some() {
+            return Err(ErrorResp::new(
+                ApiErrorCode::AlreadyCompleted,
+                "upload already completed",
+            ));
+        }
+        metadata.info = Some(info.clone());
+        metadata.completed_at = Some(OffsetDateTime::now_utc().unix_timestamp());
+        Ok(())
+    })
+    .await
+}
+
+async fn post_key_slot(
+    Path(id): Path<String>,
+    user: User,
+    State(state): State<AppState>,
+    Json(slot): Json<KeySlot>,
+) -> Result<(), ErrorResp> {
+    user.require_session()?;
+    validate_key_slot(&slot)?;
+
+    update_owned_metadata(&state, &id, &user, |metadata| {
+        if metadata.key_slots.is_empty() {
+            return Err(ErrorResp::new(
+                ApiErrorCode::BadRequest,
+                "share is not encrypted with key slots",
+            ));
+        }
+        metadata.key_slots.push(slot.clone());
+        validate_key_slots(&metadata.key_slots)
+    })
+    .await
+}
+
+/// Anyone who already unwrapped the file key can still decrypt the share, but the passphrase of
+/// the slot no longer works.
+async fn delete_key_slot(
+    Path((id, name)): Path<(String, String)>,
+    user: User,
+    State(state): State<AppState>,
+) -> Result<(), ErrorResp> {
+    user.require_session()?;
+
+    update_owned_metadata(&state, &id, &user, |metadata| {
+        let len = metadata.key_slots.len();
+        metadata.key_slots.retain(|slot| slot.name != name);
+        if metadata.key_slots.len() == len {
+            Err(ErrorResp::new(ApiErrorCode::NotFound, "key slot not found"))
+        } else if metadata.key_slots.is_empty() {
+            Err(ErrorResp::new(
+                ApiErrorCode::BadRequest,
+                "cannot remove the last key slot",
+            ))
+        } else {
+            Ok(())
+        }
+    })
+    .await
 }
 
 async fn get_file(
     Path((id, seq)): Path<(String, usize)>,
     State(state): State<AppState>,
-) -> Result<StreamBody<ByteStream>, (StatusCode, &'static str)> {
+) -> Result<StreamBody<ByteStream>, ErrorResp> {
+    if nanum_core::id::validate(&id).is_err() {
+        return Err(ErrorResp::new(ApiErrorCode::NotFound, "chunk not found"));
+    }
+
+    // the metadata is small, and chunks may be fetched directly without asking for it first
+    let metadata = s3::get_metadata(&state.s3_client, &id)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, "failed to get metadata from S3");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to get metadata from S3")
+        })?
+        .ok_or_else(|| ErrorResp::new(ApiErrorCode::NotFound, "chunk not found"))?;
+    check_downloadable(&metadata)?;
+
     let file = s3::get_file(&state.s3_client, &id, seq)
         .await
         .map_err(|error| {
             tracing::error!(%error, "failed to get file from S3");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "failed to get file from S3",
-            )
+            ErrorResp::new(ApiErrorCode::Internal, "failed to get file from S3")
         })?
-        .ok_or((StatusCode::NOT_FOUND, ""))?;
+        .ok_or_else(|| ErrorResp::new(ApiErrorCode::NotFound, "chunk not found"))?;
+    // every download starts from the first chunk
+    if seq == 1 {
+        if let Err(error) = state.records.count_download(&id).await {
+            tracing::error!(%error, %id, "failed to count download");
+        }
+    }
     Ok(StreamBody::new(file))
 }
 
 async fn post_file(
-    _user: User,
+    user: User,
     Path((id, seq)): Path<(String, usize)>,
     State(state): State<AppState>,
-    body: Bytes,
-) -> Result<(), (StatusCode, &'static str)> {
-    let existing_file = s3::get_file(&state.s3_client, &id, seq)
+    RawBody(body): RawBody,
+) -> Result<(), ErrorResp> {
+    user.require_scope(TokenScope::Upload)?;
+    validate_id(&id)?;
+
+    let created = s3::create_file(&state.s3_client, &id, seq, body.to_vec())
         .await
         .map_err(|error| {
-            tracing::error!(%error, "failed to get file from S3");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "failed to get file from S3",
-            )
+            tracing::error!(%error, "failed to upload file to S3");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to upload file to S3")
         })?;
-    if existing_file.is_some() {
-        return Err((StatusCode::CONFLICT, ""));
+    if !created {
+        return Err(ErrorResp::new(
+            ApiErrorCode::ChunkExists,
+            "chunk already uploaded",
+        ));
     }
+    Ok(())
+}
 
-    s3::upload_file(&state.s3_client, &id, seq, body.to_vec())
+async fn get_shares(
+    user: User,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ShareSummary>>, ErrorResp> {
+    user.require_session()?;
+
+    let mut shares = state
+        .records
+        .list(&user.primary_email)
         .await
         .map_err(|error| {
-            tracing::error!(%error, "failed to upload file to S3");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "failed to upload file to S3",
-            )
+            tracing::error!(%error, "failed to list share records");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to list share records")
+        })?;
+    // newest first, then shares created before timestamps were recorded
+    shares.sort_by_key(|share| std::cmp::Reverse(share.created_at));
+    Ok(Json(shares))
+}
+
+async fn delete_share(
+    user: User,
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<(), ErrorResp> {
+    user.require_session()?;
+    if nanum_core::id::validate(&id).is_err() {
+        return Err(ErrorResp::new(ApiErrorCode::NotFound, "share not found"));
+    }
+
+    let metadata = s3::get_metadata(&state.s3_client, &id)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, "failed to get metadata from S3");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to get metadata from S3")
+        })?
+        .ok_or_else(|| ErrorResp::new(ApiErrorCode::NotFound, "share not found"))?;
+    if metadata.creator_email != user.primary_email {
+        return Err(ErrorResp::new(ApiErrorCode::NotFound, "share not found"));
+    }
+    // disabled shares are kept for the administrator to look into
+    if metadata.disabled_at.is_some() {
+        return Err(ErrorResp::new(ApiErrorCode::Disabled, "share is disabled"));
+    }
+
+    // the metadata goes first so that the share is gone even if deleting chunks fails halfway
+    s3::delete_metadata(&state.s3_client, &id)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, "failed to delete metadata from S3");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to delete metadata from S3")
+        })?;
+    sync_record(&state, &id, None).await;
+    s3::delete_files(&state.s3_client, &id)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, %id, "failed to delete chunks from S3");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to delete chunks from S3")
+        })?;
+    Ok(())
+}
+
+async fn get_tokens(
+    user: User,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ApiTokenInfo>>, ErrorResp> {
+    user.require_session()?;
+
+    let mut tokens = s3::list_tokens(&state.s3_client)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, "failed to list API tokens from S3");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to list API tokens from S3")
+        })?
+        .into_iter()
+        .filter(|token| token.creator_email == user.primary_email)
+        .map(|token| token.info())
+        .collect::<Vec<_>>();
+    tokens.sort_by_key(|token| token.created_at);
+    Ok(Json(tokens))
+}
+
+const MAX_TOKEN_NAME_LENGTH: usize = 100;
+const MAX_TOKEN_EXPIRES_IN_DAYS: u32 = 365;
+
+async fn post_token(
+    user: User,
+    State(state): State<AppState>,
+    Json(req): Json<ApiTokenCreationReq>,
+) -> Result<Json<ApiTokenCreationResp>, ErrorResp> {
+    user.require_session()?;
+
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > MAX_TOKEN_NAME_LENGTH {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "invalid token name",
+        ));
+    }
+    if req.scopes.is_empty() {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "token scopes are empty",
+        ));
+    }
+    if req.expires_in_days == 0 || req.expires_in_days > MAX_TOKEN_EXPIRES_IN_DAYS {
+        return Err(ErrorResp::new(
+            ApiErrorCode::BadRequest,
+            "invalid token expiration",
+        ));
+    }
+
+    let generated = nanum_core::token::generate().map_err(|error| {
+        tracing::error!(%error, "failed to generate API token");
+        ErrorResp::new(ApiErrorCode::Internal, "failed to generate API token")
+    })?;
+
+    let now = OffsetDateTime::now_utc();
+    let api_token = ApiToken {
+        id: generated.id,
+        name: name.to_string(),
+        creator_email: user.primary_email,
+        secret_hash: generated.secret_hash,
+        scopes: req.scopes,
+        created_at: now.unix_timestamp(),
+        expires_at: (now + Duration::days(req.expires_in_days.into())).unix_timestamp(),
+        last_used_at: None,
+    };
+    let created = s3::create_token(&state.s3_client, &api_token)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, "failed to upload API token to S3");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to upload API token to S3")
+        })?;
+    if !created {
+        return Err(ErrorResp::new(
+            ApiErrorCode::Internal,
+            "generated API token ID is already taken",
+        ));
+    }
+
+    Ok(Json(ApiTokenCreationResp {
+        token: generated.token,
+        info: api_token.info(),
+    }))
+}
+
+async fn delete_token(
+    user: User,
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<(), ErrorResp> {
+    user.require_session()?;
+
+    let api_token = s3::get_token(&state.s3_client, &id)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, "failed to get API token from S3");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to get API token from S3")
+        })?
+        .ok_or_else(|| ErrorResp::new(ApiErrorCode::NotFound, "API token not found"))?;
+    if api_token.creator_email != user.primary_email {
+        return Err(ErrorResp::new(
+            ApiErrorCode::NotFound,
+            "API token not found",
+        ));
+    }
+
+    s3::delete_token(&state.s3_client, &id)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, "failed to delete API token from S3");
+            ErrorResp::new(ApiErrorCode::Internal, "failed to delete API token from S3")
         })?;
     Ok(())
 }
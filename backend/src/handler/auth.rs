@@ -1,26 +1,46 @@
+use std::{sync::Arc, time::Instant};
+
 use anyhow::Result;
 use axum::{
     async_trait,
-    extract::{FromRequestParts, Query, State},
-    headers,
-    http::{header, request::Parts, StatusCode},
+    extract::{FromRef, FromRequestParts, OriginalUri, Query, State},
+    headers::{self, HeaderMapExt},
+    http::{header, request::Parts, Request, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Redirect, Response},
     routing, RequestPartsExt, Router, TypedHeader,
 };
 use headers::HeaderMap;
 use jsonwebtoken::{decode, encode, Validation};
+use nanum_core::{
+    error::ApiErrorCode,
+    types::{ApiToken, Revocations, TokenScope},
+};
 use oauth2::{
     basic::BasicClient, reqwest::async_http_client, AuthUrl, AuthorizationCode, ClientId,
     ClientSecret, CsrfToken, RedirectUrl, Scope, TokenResponse, TokenUrl,
 };
 use serde::{Deserialize, Serialize};
 use time::{Duration, OffsetDateTime};
+use tokio::sync::RwLock;
 
-use crate::config::CONFIG;
+use crate::{config::CONFIG, s3};
 
-use super::AppState;
+use super::{error::ErrorResp, AppState};
 
 static SESSION_COOKIE_NAME: &str = "session";
+static REFRESH_COOKIE_NAME: &str = "refresh";
+
+/// Lifetime of an access token. Expired access tokens are silently refreshed.
+const SESSION_DURATION: Duration = Duration::minutes(15);
+/// A refresh token expires if it is not used within this duration.
+const REFRESH_DURATION: Duration = Duration::days(7);
+/// Sessions can be refreshed until this long after the GitHub login.
+const MAX_SESSION_LIFETIME: Duration = Duration::days(30);
+/// `last_used_at` of API tokens is updated at most once in this duration.
+const TOKEN_LAST_USED_GRANULARITY: Duration = Duration::minutes(1);
+
+const REVOCATIONS_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
 
 pub fn create_oauth_client() -> BasicClient {
     BasicClient::new(
@@ -35,6 +55,46 @@ pub fn create_router() -> Router<AppState> {
     Router::new()
         .route("/github", routing::get(handle_get_github))
         .route("/authorized", routing::get(handle_get_authorized))
+        .route("/logout", routing::post(handle_post_logout))
+}
+
+fn session_cookie(name: &str, value: &str, max_age: Duration) -> String {
+    let mut cookie = format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        name,
+        value,
+        max_age.whole_seconds()
+    );
+    if CONFIG.public_url.scheme() == "https" {
+        cookie.push_str("; Secure");
+    }
+    cookie
+}
+
+fn is_allowed(emails: &[String]) -> bool {
+    CONFIG
+        .allowed_emails
+        .iter()
+        .any(|allowed_email| emails.contains(allowed_email))
+}
+
+#[derive(Default)]
+pub struct RevocationsCache {
+    cached: RwLock<Option<(Instant, Arc<Revocations>)>>,
+}
+
+impl RevocationsCache {
+    async fn get(&self, s3_client: &aws_sdk_s3::Client) -> Result<Arc<Revocations>> {
+        if let Some((fetched_at, revocations)) = &*self.cached.read().await {
+            if fetched_at.elapsed() < REVOCATIONS_CACHE_TTL {
+                return Ok(revocations.clone());
+            }
+        }
+
+        let revocations = Arc::new(s3::get_revocations(s3_client).await?);
+        *self.cached.write().await = Some((Instant::now(), revocations.clone()));
+        Ok(revocations)
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -42,21 +102,141 @@ pub fn create_router() -> Router<AppState> {
 pub struct User {
     pub primary_email: String,
     pub emails: Vec<String>,
+    pub auth_time: i64,
     pub exp: i64,
+    /// Scopes granted to the API token used for the request. `None` for browser sessions.
+    #[serde(skip)]
+    pub token_scopes: Option<Vec<TokenScope>>,
 }
 
-pub enum UserRejection {
+impl User {
+    pub fn require_scope(&self, scope: TokenScope) -> Result<(), ErrorResp> {
+        match &self.token_scopes {
+            Some(scopes) if !scopes.contains(&scope) => Err(ErrorResp::new(
+                ApiErrorCode::Forbidden,
+                "API token does not have required scope",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn require_session(&self) -> Result<(), ErrorResp> {
+        if self.token_scopes.is_some() {
+            Err(ErrorResp::new(
+                ApiErrorCode::Forbidden,
+                "not allowed with API token",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshClaims {
+    sub: String,
+    emails: Vec<String>,
+    auth_time: i64,
+    exp: i64,
+}
+
+struct SessionTokens {
+    session: String,
+    refresh: String,
+    refresh_max_age: Duration,
+}
+
+impl SessionTokens {
+    fn issue(primary_email: String, emails: Vec<String>, auth_time: i64) -> Result<Self> {
+        let now = OffsetDateTime::now_utc();
+        let refresh_exp = (now + REFRESH_DURATION)
+            .unix_timestamp()
+            .min(auth_time + MAX_SESSION_LIFETIME.whole_seconds());
+
+        let refresh_claims = RefreshClaims {
+            sub: primary_email.clone(),
+            emails: emails.clone(),
+            auth_time,
+            exp: refresh_exp,
+        };
+        let user = User {
+            primary_email,
+            emails,
+            auth_time,
+            exp: (now + SESSION_DURATION).unix_timestamp(),
+            token_scopes: None,
+        };
+
+        Ok(Self {
+            session: encode(&Default::default(), &user, &CONFIG.jwt_secret.0)?,
+            refresh: encode(&Default::default(), &refresh_claims, &CONFIG.jwt_secret.0)?,
+            refresh_max_age: Duration::seconds(refresh_exp - now.unix_timestamp()),
+        })
+    }
+
+    fn set_cookie_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::SET_COOKIE,
+            session_cookie(SESSION_COOKIE_NAME, &self.session, SESSION_DURATION)
+                .parse()
+                .unwrap(),
+        );
+        headers.append(
+            header::SET_COOKIE,
+            session_cookie(REFRESH_COOKIE_NAME, &self.refresh, self.refresh_max_age)
+                .parse()
+                .unwrap(),
+        );
+        headers
+    }
+}
+
+fn decode_session<T: serde::de::DeserializeOwned>(token: &str) -> jsonwebtoken::errors::Result<T> {
+    let mut jwt_validation = Validation::default();
+    jwt_validation.validate_exp = true;
+    decode::<T>(token, &CONFIG.jwt_secret.1, &jwt_validation).map(|data| data.claims)
+}
+
+enum RejectionReason {
     NotAuthorized,
     Error(&'static str),
     Forbidden,
 }
 
+pub struct UserRejection {
+    reason: RejectionReason,
+    /// API calls get a JSON error instead of being redirected to the sign in page.
+    api: bool,
+}
+
 impl IntoResponse for UserRejection {
     fn into_response(self) -> Response {
-        match self {
-            Self::NotAuthorized => Redirect::to("/auth/github").into_response(),
-            Self::Error(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response(),
-            Self::Forbidden => (StatusCode::FORBIDDEN, "user not allowed").into_response(),
+        let (status, code, message) = match self.reason {
+            RejectionReason::NotAuthorized if !self.api => {
+                return Redirect::to("/auth/github").into_response()
+            }
+            RejectionReason::NotAuthorized => (
+                StatusCode::UNAUTHORIZED,
+                ApiErrorCode::Unauthorized,
+                "sign in required",
+            ),
+            RejectionReason::Error(msg) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ApiErrorCode::Internal,
+                msg,
+            ),
+            RejectionReason::Forbidden => (
+                StatusCode::FORBIDDEN,
+                ApiErrorCode::UserNotAllowed,
+                "user not allowed",
+            ),
+        };
+        if self.api {
+            ErrorResp::new(code, message).into_response()
+        } else {
+            (status, message).into_response()
         }
     }
 }
@@ -64,38 +244,187 @@ impl IntoResponse for UserRejection {
 #[async_trait]
 impl<S> FromRequestParts<S> for User
 where
+    AppState: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = UserRejection;
 
-    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let api = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map_or(&parts.uri, |original_uri| &original_uri.0)
+            .path()
+            .starts_with("/api/");
+        authenticate(parts, &AppState::from_ref(state))
+            .await
+            .map_err(|reason| UserRejection { reason, api })
+    }
+}
+
+async fn authenticate(parts: &mut Parts, state: &AppState) -> Result<User, RejectionReason> {
+    let bearer = parts
+        .headers
+        .typed_get::<headers::Authorization<headers::authorization::Bearer>>();
+    let user = if let Some(bearer) = bearer {
+        authenticate_api_token(state, bearer.token()).await?
+    } else {
         let cookies: Option<TypedHeader<headers::Cookie>> =
             parts.extract().await.map_err(|error| {
                 tracing::error!(%error, "failed to extract Cookie header");
-                UserRejection::Error("failed to extract Cookie header")
+                RejectionReason::Error("failed to extract Cookie header")
             })?;
         let session_cookie = cookies
             .as_ref()
             .and_then(|cookies| cookies.get(SESSION_COOKIE_NAME))
-            .ok_or(UserRejection::NotAuthorized)?;
-
-        let mut jwt_validation = Validation::default();
-        jwt_validation.validate_exp = true;
-        let user_data = decode::<User>(session_cookie, &CONFIG.jwt_secret.1, &jwt_validation)
-            .map_err(|error| {
-                tracing::error!(%error, "failed to decode JWT session token");
-                UserRejection::NotAuthorized
-            })?;
-        let user = user_data.claims;
+            .ok_or(RejectionReason::NotAuthorized)?;
+
+        decode_session::<User>(session_cookie).map_err(|error| {
+            tracing::error!(%error, "failed to decode JWT session token");
+            RejectionReason::NotAuthorized
+        })?
+    };
+
+    let revocations = state
+        .revocations
+        .get(&state.s3_client)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, "failed to get revocations from S3");
+            RejectionReason::Error("failed to get revocations from S3")
+        })?;
+    if revocations.is_revoked(&user.emails, user.auth_time) {
+        return Err(RejectionReason::NotAuthorized);
+    }
 
-        for allowed_email in &CONFIG.allowed_emails {
-            if user.emails.contains(allowed_email) {
-                return Ok(user);
+    if is_allowed(&user.emails) {
+        Ok(user)
+    } else {
+        Err(RejectionReason::Forbidden)
+    }
+}
+
+async fn authenticate_api_token(state: &AppState, token: &str) -> Result<User, RejectionReason> {
+    let (id, secret) = nanum_core::token::parse(token).ok_or(RejectionReason::NotAuthorized)?;
+    let (mut api_token, etag) = s3::get_token_with_etag(&state.s3_client, id)
+        .await
+        .map_err(|error| {
+            tracing::error!(%error, "failed to get API token from S3");
+            RejectionReason::Error("failed to get API token from S3")
+        })?
+        .ok_or(RejectionReason::NotAuthorized)?;
+
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    if !nanum_core::token::verify(secret, &api_token.secret_hash) || api_token.expires_at <= now {
+        return Err(RejectionReason::NotAuthorized);
+    }
+
+    let last_used_stale = api_token.last_used_at.is_none_or(|last_used_at| {
+        now - last_used_at >= TOKEN_LAST_USED_GRANULARITY.whole_seconds()
+    });
+    if last_used_stale {
+        api_token.last_used_at = Some(now);
+        let s3_client = state.s3_client.clone();
+        let api_token = api_token.clone();
+        // a token deleted in the meantime must not be written back
+        tokio::spawn(async move {
+            match s3::update_token(&s3_client, &api_token, &etag).await {
+                Ok(true) => {}
+                Ok(false) => tracing::debug!(id = %api_token.id, "API token modified concurrently"),
+                Err(error) => tracing::error!(%error, "failed to update API token last used time"),
             }
+        });
+    }
+
+    let ApiToken {
+        creator_email,
+        scopes,
+        created_at,
+        expires_at,
+        ..
+    } = api_token;
+    Ok(User {
+        primary_email: creator_email.clone(),
+        emails: vec![creator_email],
+        auth_time: created_at,
+        exp: expires_at,
+        token_scopes: Some(scopes),
+    })
+}
+
+async fn refresh_session(state: &AppState, cookies: &headers::Cookie) -> Option<SessionTokens> {
+    let refresh_token = cookies.get(REFRESH_COOKIE_NAME)?;
+    let claims = match decode_session::<RefreshClaims>(refresh_token) {
+        Ok(claims) => claims,
+        Err(error) => {
+            tracing::debug!(%error, "failed to decode JWT refresh token");
+            return None;
         }
+    };
+
+    if !is_allowed(&claims.emails) {
+        return None;
+    }
+    match state.revocations.get(&state.s3_client).await {
+        Ok(revocations) if !revocations.is_revoked(&claims.emails, claims.auth_time) => {}
+        Ok(_) => return None,
+        Err(error) => {
+            tracing::error!(%error, "failed to get revocations from S3");
+            return None;
+        }
+    }
+
+    SessionTokens::issue(claims.sub, claims.emails, claims.auth_time)
+        .map_err(|error| {
+            tracing::error!(%error, "failed to encode JWT session token");
+        })
+        .ok()
+}
+
+/// Issues a new access token from the refresh token when the access token is missing or expired.
+pub async fn refresh_session_middleware<B>(
+    State(state): State<AppState>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let cookies = req.headers().typed_get::<headers::Cookie>();
+    let refreshed = if let Some(cookies) = &cookies {
+        let session_valid = cookies
+            .get(SESSION_COOKIE_NAME)
+            .is_some_and(|token| decode_session::<User>(token).is_ok());
+        if session_valid {
+            None
+        } else {
+            refresh_session(&state, cookies).await
+        }
+    } else {
+        None
+    };
+
+    if let (Some(cookies), Some(tokens)) = (&cookies, &refreshed) {
+        // Replace the stale tokens so that extractors of this request see the refreshed session.
+        let cookie = cookies
+            .iter()
+            .filter(|(name, _)| *name != SESSION_COOKIE_NAME && *name != REFRESH_COOKIE_NAME)
+            .map(|(name, value)| format!("{name}={value}"))
+            .chain([
+                format!("{}={}", SESSION_COOKIE_NAME, tokens.session),
+                format!("{}={}", REFRESH_COOKIE_NAME, tokens.refresh),
+            ])
+            .collect::<Vec<_>>()
+            .join("; ");
+        req.headers_mut()
+            .insert(header::COOKIE, cookie.parse().unwrap());
+    }
 
-        Err(UserRejection::Forbidden)
+    let mut resp = next.run(req).await;
+    if let Some(tokens) = refreshed {
+        // Cookies set by the handler itself (e.g. logout) take precedence.
+        if !resp.headers().contains_key(header::SET_COOKIE) {
+            resp.headers_mut().extend(tokens.set_cookie_headers());
+        }
     }
+    resp
 }
 
 #[derive(Deserialize)]
@@ -188,16 +517,8 @@ async fn handle_get_authorized(
     let primary_email = primary_email.unwrap_or_else(|| emails[0].clone());
 
     let now = OffsetDateTime::now_utc();
-    let exp = (now + Duration::days(1)).unix_timestamp();
-
-    let user = User {
-        primary_email,
-        emails,
-        exp,
-    };
-
-    let session_token =
-        encode(&Default::default(), &user, &CONFIG.jwt_secret.0).map_err(|error| {
+    let tokens =
+        SessionTokens::issue(primary_email, emails, now.unix_timestamp()).map_err(|error| {
             tracing::error!(%error, "failed to encode JWT session token");
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -205,13 +526,17 @@ async fn handle_get_authorized(
             )
         })?;
 
-    let cookie = format!(
-        "{}={}; SameSite=Lax; Path=/",
-        SESSION_COOKIE_NAME, session_token
-    );
+    Ok((tokens.set_cookie_headers(), Redirect::to("/")))
+}
 
+async fn handle_post_logout() -> (HeaderMap, Redirect) {
     let mut headers = HeaderMap::new();
-    headers.insert(header::SET_COOKIE, cookie.parse().unwrap());
+    for name in [SESSION_COOKIE_NAME, REFRESH_COOKIE_NAME] {
+        headers.append(
+            header::SET_COOKIE,
+            session_cookie(name, "", Duration::ZERO).parse().unwrap(),
+        );
+    }
 
-    Ok((headers, Redirect::to("/")))
+    (headers, Redirect::to("/"))
 }
@@ -0,0 +1,88 @@
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{
+        rejection::{BytesRejection, JsonRejection, PathRejection},
+        FromRequest, FromRequestParts,
+    },
+    http::{Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use nanum_core::error::{ApiError, ApiErrorCode};
+use serde::Serialize;
+
+/// JSON error response of API handlers.
+pub struct ErrorResp(pub ApiError);
+
+impl ErrorResp {
+    pub fn new(code: ApiErrorCode, message: impl Into<String>) -> Self {
+        Self(ApiError::new(code, message))
+    }
+}
+
+impl IntoResponse for ErrorResp {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.0.code.status_code())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, axum::Json(self.0)).into_response()
+    }
+}
+
+fn rejection_code(status: StatusCode) -> ApiErrorCode {
+    if status == StatusCode::PAYLOAD_TOO_LARGE {
+        ApiErrorCode::PayloadTooLarge
+    } else if status.is_server_error() {
+        ApiErrorCode::Internal
+    } else {
+        ApiErrorCode::BadRequest
+    }
+}
+
+impl From<JsonRejection> for ErrorResp {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection_code(rejection.status()), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ErrorResp {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection_code(rejection.status()), rejection.body_text())
+    }
+}
+
+impl From<BytesRejection> for ErrorResp {
+    fn from(rejection: BytesRejection) -> Self {
+        Self::new(rejection_code(rejection.status()), rejection.body_text())
+    }
+}
+
+/// `axum::Json` which rejects with `ErrorResp`.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ErrorResp))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// `axum::extract::Path` which rejects with `ErrorResp`.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ErrorResp))]
+pub struct Path<T>(pub T);
+
+/// Raw request body which rejects with `ErrorResp`.
+pub struct RawBody(pub Bytes);
+
+#[async_trait]
+impl<S> FromRequest<S, Body> for RawBody
+where
+    S: Send + Sync,
+{
+    type Rejection = ErrorResp;
+
+    async fn from_request(req: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(Bytes::from_request(req, state).await?))
+    }
+}
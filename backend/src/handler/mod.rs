@@ -1,7 +1,11 @@
 mod api;
 mod auth;
+mod error;
+mod rate_limit;
 mod statics;
 
+use std::sync::Arc;
+
 use axum::{
     extract::DefaultBodyLimit,
     http::{header, Request},
@@ -10,23 +14,39 @@ use axum::{
     routing, Router,
 };
 
-use self::auth::User;
+use crate::records::ShareRecords;
+
+use self::{
+    auth::{RevocationsCache, User},
+    rate_limit::RateLimiter,
+};
 
 #[derive(Clone)]
 pub struct AppState {
     s3_client: aws_sdk_s3::Client,
     http_client: reqwest::Client,
     oauth_client: oauth2::basic::BasicClient,
+    revocations: Arc<RevocationsCache>,
+    rate_limiter: Arc<RateLimiter>,
+    records: ShareRecords,
 }
 
-pub fn create_router(s3_client: aws_sdk_s3::Client, http_client: reqwest::Client) -> Router {
+pub fn create_router(
+    s3_client: aws_sdk_s3::Client,
+    http_client: reqwest::Client,
+    records: ShareRecords,
+) -> Router {
     let oauth_client = auth::create_oauth_client();
 
     let state = AppState {
         s3_client,
         http_client,
         oauth_client,
+        revocations: Default::default(),
+        rate_limiter: Default::default(),
+        records,
     };
+    state.rate_limiter.clone().spawn_sweeper();
 
     let api = api::create_router();
     let auth = auth::create_router();
@@ -35,11 +55,21 @@ pub fn create_router(s3_client: aws_sdk_s3::Client, http_client: reqwest::Client
     Router::new()
         .nest("/api", api)
         .layer(DefaultBodyLimit::max(1024 * 1024 * 11))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit_middleware,
+        ))
         .nest("/auth", auth)
-        .with_state(state)
         .layer(tower_http::trace::TraceLayer::new_for_http())
         .route("/", routing::get(get_frontend_index))
+        .route("/tokens", routing::get(get_frontend_index))
+        .route("/shares", routing::get(get_frontend_index))
         .route("/:id", routing::get(get_frontend_download))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth::refresh_session_middleware,
+        ))
+        .with_state(state)
         .nest("/static", statics)
         .layer(axum::middleware::from_fn(server_header_middleware))
 }
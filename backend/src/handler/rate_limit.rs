@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use nanum_core::error::ApiErrorCode;
+
+use crate::config::CONFIG;
+
+use super::{error::ErrorResp, AppState};
+
+/// How often refilled buckets are dropped, which bounds the memory without scanning every bucket
+/// on each request.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token buckets per client IP, or per /64 for IPv6 since a single client usually owns a whole
+/// /64.
+pub struct TokenBuckets {
+    capacity: f64,
+    per_second: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl TokenBuckets {
+    fn new(burst: u32, per_minute: f64) -> Self {
+        Self {
+            capacity: burst as f64,
+            per_second: per_minute / 60.,
+            buckets: Default::default(),
+        }
+    }
+
+    fn is_disabled(&self) -> bool {
+        self.capacity <= 0. || self.per_second <= 0.
+    }
+
+    /// Refills the bucket of `ip` and calls `f` with its tokens.
+    fn with_bucket<T>(&self, ip: IpAddr, f: impl FnOnce(&mut f64) -> T) -> T {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(bucket_key(ip)).or_insert(Bucket {
+            tokens: self.capacity,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.capacity);
+        bucket.updated_at = now;
+        f(&mut bucket.tokens)
+    }
+
+    fn retry_after(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64((1. - tokens) / self.per_second)
+    }
+
+    /// Fails with the time to wait if `ip` has no token left, without taking one.
+    fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        if self.is_disabled() {
+            return Ok(());
+        }
+        self.with_bucket(ip, |tokens| {
+            if *tokens >= 1. {
+                Ok(())
+            } else {
+                Err(self.retry_after(*tokens))
+            }
+        })
+    }
+
+    /// Takes a token of `ip`, or fails with the time to wait.
+    fn take(&self, ip: IpAddr) -> Result<(), Duration> {
+        if self.is_disabled() {
+            return Ok(());
+        }
+        self.with_bucket(ip, |tokens| {
+            if *tokens >= 1. {
+                *tokens -= 1.;
+                Ok(())
+            } else {
+                Err(self.retry_after(*tokens))
+            }
+        })
+    }
+
+    /// Takes a token of `ip` even if none is left, so that the debt has to be waited out.
+    fn spend(&self, ip: IpAddr) {
+        if !self.is_disabled() {
+            self.with_bucket(ip, |tokens| *tokens = (*tokens - 1.).max(-self.capacity));
+        }
+    }
+
+    /// Drops buckets which are full again, since they are the same as a missing one.
+    fn sweep(&self) {
+        let now = Instant::now();
+        self.buckets.lock().unwrap().retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens + elapsed * self.per_second < self.capacity
+        });
+    }
+}
+
+/// Clients are told apart by IPv4 address or by IPv6 /64 prefix.
+fn bucket_key(ip: IpAddr) -> IpAddr {
+    match ip.to_canonical() {
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            IpAddr::V6(Ipv6Addr::new(
+                segments[0],
+                segments[1],
+                segments[2],
+                segments[3],
+                0,
+                0,
+                0,
+                0,
+            ))
+        }
+        ip => ip,
+    }
+}
+
+pub struct RateLimiter {
+    not_found: TokenBuckets,
+    download: TokenBuckets,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            not_found: TokenBuckets::new(
+                CONFIG.rate_limit_not_found_burst,
+                CONFIG.rate_limit_not_found_per_minute,
+            ),
+            download: TokenBuckets::new(
+                CONFIG.rate_limit_download_burst,
+                CONFIG.rate_limit_download_per_minute,
+            ),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Periodically drops buckets which are full again.
+    pub fn spawn_sweeper(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.not_found.sweep();
+                self.download.sweep();
+            }
+        });
+    }
+}
+
+/// Address of the client, taken from `X-Forwarded-For` if the server is behind
+/// `trusted_proxy_count` trusted proxies.
+///
+/// Each proxy appends the address of its peer, so the client is the one appended by the
+/// outermost trusted proxy. Entries before it may be forged by the client.
+fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxy_count: usize) -> IpAddr {
+    if trusted_proxy_count == 0 {
+        return peer;
+    }
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    forwarded
+        .iter()
+        .rev()
+        .nth(trusted_proxy_count - 1)
+        .and_then(|ip| ip.parse().ok())
+        .unwrap_or(peer)
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let secs = retry_after.as_secs_f64().ceil().max(1.) as u64;
+    (
+        [(header::RETRY_AFTER, secs.to_string())],
+        ErrorResp::new(ApiErrorCode::TooManyRequests, "too many requests"),
+    )
+        .into_response()
+}
+
+/// Limits chunk downloads and requests answered with 404 of each client.
+pub async fn rate_limit_middleware<B>(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let limiter = &state.rate_limiter;
+    let ip = client_ip(peer.ip(), req.headers(), CONFIG.trusted_proxy_count);
+
+    if let Err(retry_after) = limiter.not_found.check(ip) {
+        tracing::warn!(%ip, "too many requests for missing resources");
+        return too_many_requests(retry_after);
+    }
+    if req.method() == Method::GET && req.uri().path().starts_with("/api/file/") {
+        if let Err(retry_after) = limiter.download.take(ip) {
+            tracing::warn!(%ip, "too many downloads");
+            return too_many_requests(retry_after);
+        }
+    }
+
+    let resp = next.run(req).await;
+    if resp.status() == StatusCode::NOT_FOUND {
+        limiter.not_found.spend(ip);
+    }
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded_for(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn client_ip_ignores_header_without_trusted_proxies() {
+        let peer = "10.0.0.1".parse().unwrap();
+        let headers = forwarded_for(&["1.2.3.4"]);
+        assert_eq!(client_ip(peer, &headers, 0), peer);
+    }
+
+    #[test]
+    fn client_ip_takes_entry_of_outermost_trusted_proxy() {
+        let peer = "10.0.0.1".parse().unwrap();
+        // the client forged the first entry
+        let headers = forwarded_for(&["6.6.6.6, 1.2.3.4", "10.0.0.2"]);
+        assert_eq!(
+            client_ip(peer, &headers, 1),
+            "10.0.0.2".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            client_ip(peer, &headers, 2),
+            "1.2.3.4".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn client_ip_falls_back_to_peer() {
+        let peer = "10.0.0.1".parse().unwrap();
+        assert_eq!(client_ip(peer, &HeaderMap::new(), 1), peer);
+        assert_eq!(client_ip(peer, &forwarded_for(&["1.2.3.4"]), 2), peer);
+        assert_eq!(client_ip(peer, &forwarded_for(&["garbage"]), 1), peer);
+    }
+
+    #[test]
+    fn ipv6_clients_share_a_bucket_per_64() {
+        let a = "2001:db8:1:2:aaaa::1".parse().unwrap();
+        let b = "2001:db8:1:2:bbbb::2".parse().unwrap();
+        let c = "2001:db8:1:3::1".parse().unwrap();
+        assert_eq!(bucket_key(a), bucket_key(b));
+        assert_ne!(bucket_key(a), bucket_key(c));
+
+        let v4 = "1.2.3.4".parse().unwrap();
+        let mapped = "::ffff:1.2.3.4".parse().unwrap();
+        assert_eq!(bucket_key(v4), v4);
+        assert_eq!(bucket_key(mapped), v4);
+    }
+
+    #[test]
+    fn buckets_run_out_and_sweep_drops_full_ones() {
+        let buckets = TokenBuckets::new(2, 1.);
+        let ip = "1.2.3.4".parse().unwrap();
+        assert!(buckets.take(ip).is_ok());
+        assert!(buckets.take(ip).is_ok());
+        assert!(buckets.take(ip).is_err());
+        assert!(buckets.check(ip).is_err());
+        assert!(buckets.check("1.2.3.5".parse().unwrap()).is_ok());
+
+        buckets.sweep();
+        // only the empty bucket is kept
+        assert_eq!(buckets.buckets.lock().unwrap().len(), 1);
+    }
+}
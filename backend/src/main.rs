@@ -1,12 +1,17 @@
 mod config;
+#[cfg(feature = "sqlite")]
+mod db;
 mod handler;
+mod records;
 mod s3;
 
+use std::net::SocketAddr;
+
 use anyhow::{Context, Result};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
-use crate::config::CONFIG;
+use crate::{config::CONFIG, records::ShareRecords};
 
 async fn shutdown_signal() {
     let ctrl_c = async {
@@ -59,17 +64,22 @@ async fn main() -> Result<()> {
         .build()
         .unwrap();
 
-    let router = crate::handler::create_router(s3_client, http_client);
+    let records = ShareRecords::open(&s3_client).await?;
+    let router = crate::handler::create_router(s3_client, http_client, records);
 
     let listen_addr = CONFIG
         .listen_addr
         .parse()
         .context("failed to parse listen addr")?;
 
+    tracing::info!(
+        bits = CONFIG.random_uri_entropy(),
+        "entropy of random share IDs"
+    );
     tracing::info!(%listen_addr, "starting http server...");
 
     axum::Server::bind(&listen_addr)
-        .serve(router.into_make_service())
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_signal())
         .await
         .context("failed to serve HTTP")?;
@@ -0,0 +1,199 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use nanum_core::types::{IndexEntry, ShareSummary};
+
+use crate::s3;
+
+/// How often a dirty index is rebuilt from metadata.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often the database checks `index.json` for changes made by `nanum-admin`.
+#[cfg(feature = "sqlite")]
+const FOLLOW_INDEX_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Share records, which list shares without fetching each metadata.
+///
+/// `index.json` in the bucket is always kept up to date, since `nanum-admin` reads and updates
+/// it. With a database, records are also kept there, which counts downloads and follows changes
+/// of the index.
+///
+/// A failed update marks the records dirty instead of failing the request, since the share
+/// itself is already saved or deleted. Dirty records are rebuilt from metadata in the background.
+#[derive(Clone)]
+pub struct ShareRecords {
+    s3_client: aws_sdk_s3::Client,
+    dirty: Arc<AtomicBool>,
+    #[cfg(feature = "sqlite")]
+    db: Option<crate::db::Database>,
+}
+
+impl ShareRecords {
+    /// Creates the index if there is none yet, and opens the database if configured.
+    pub async fn open(s3_client: &aws_sdk_s3::Client) -> Result<Self> {
+        // shares uploaded before the index existed
+        s3::create_index_if_missing(s3_client)
+            .await
+            .context("failed to create share index")?;
+        let records = Self {
+            s3_client: s3_client.clone(),
+            dirty: Default::default(),
+            #[cfg(feature = "sqlite")]
+            db: match &crate::config::CONFIG.database_path {
+                Some(path) => Some(crate::db::Database::open(path)?),
+                None => None,
+            },
+        };
+        #[cfg(feature = "sqlite")]
+        if records.db.is_some() {
+            let etag = records.follow_index().await?;
+            records.spawn_index_follower(etag);
+        }
+        records.spawn_reconciler();
+        Ok(records)
+    }
+
+    /// Records `entry` of a share, or marks the records dirty.
+    pub async fn put(&self, id: &str, entry: IndexEntry) {
+        let result = s3::update_index(&self.s3_client, |index| {
+            index.shares.insert(id.to_string(), entry.clone());
+        })
+        .await;
+        self.check(id, result);
+        #[cfg(feature = "sqlite")]
+        if let Some(db) = &self.db {
+            self.check(id, db.put_share(id.to_string(), entry).await);
+        }
+    }
+
+    /// Removes the record of a share, or marks the records dirty.
+    pub async fn remove(&self, id: &str) {
+        let result = s3::update_index(&self.s3_client, |index| {
+            index.shares.remove(id);
+        })
+        .await;
+        self.check(id, result);
+        #[cfg(feature = "sqlite")]
+        if let Some(db) = &self.db {
+            self.check(id, db.remove_share(id.to_string()).await);
+        }
+    }
+
+    fn check(&self, id: &str, result: Result<()>) {
+        if let Err(error) = result {
+            tracing::error!(%error, %id, "failed to update share record, rebuilding records");
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Rebuilds the index from metadata, and the database from the index.
+    async fn reconcile(&self) -> Result<()> {
+        if !s3::rebuild_index(&self.s3_client).await? {
+            anyhow::bail!("share index modified concurrently");
+        }
+        #[cfg(feature = "sqlite")]
+        if self.db.is_some() {
+            self.follow_index().await?;
+        }
+        Ok(())
+    }
+
+    /// Periodically rebuilds the records once they are dirty.
+    fn spawn_reconciler(&self) {
+        let records = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+            loop {
+                interval.tick().await;
+                if !records.dirty.swap(false, Ordering::Relaxed) {
+                    continue;
+                }
+                match records.reconcile().await {
+                    Ok(()) => tracing::info!("rebuilt share records"),
+                    Err(error) => {
+                        tracing::error!(%error, "failed to rebuild share records");
+                        records.dirty.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        });
+    }
+
+    pub async fn list(&self, creator_email: &str) -> Result<Vec<ShareSummary>> {
+        #[cfg(feature = "sqlite")]
+        if let Some(db) = &self.db {
+            return db.list_shares(creator_email.to_string()).await;
+        }
+        let index = s3::get_index(&self.s3_client)
+            .await?
+            .context("share index is missing, run `nanum-admin reindex`")?;
+        Ok(index
+            .shares
+            .into_iter()
+            .filter(|(_, entry)| entry.creator_email == creator_email)
+            .map(|(id, entry)| entry.summary(id))
+            .collect())
+    }
+
+    /// Counts a download of a share. Only the database keeps counters, since concurrent updates
+    /// of the bucket would lose counts.
+    #[cfg_attr(not(feature = "sqlite"), allow(unused_variables))]
+    pub async fn count_download(&self, id: &str) -> Result<()> {
+        #[cfg(feature = "sqlite")]
+        if let Some(db) = &self.db {
+            return db.count_download(id.to_string()).await;
+        }
+        Ok(())
+    }
+
+    /// Replaces the records in the database with the index, keeping download counters.
+    #[cfg(feature = "sqlite")]
+    async fn follow_index(&self) -> Result<String> {
+        let db = match &self.db {
+            Some(db) => db,
+            None => anyhow::bail!("no database to sync"),
+        };
+        let (index, etag) = s3::get_index_with_etag(&self.s3_client)
+            .await?
+            .context("share index is missing, run `nanum-admin reindex`")?;
+        tracing::info!(count = index.shares.len(), "syncing shares into database");
+        db.sync_shares(index.shares).await?;
+        Ok(etag)
+    }
+
+    /// Periodically syncs the database with the index once it changes from `synced_etag`, so
+    /// that changes by `nanum-admin` show up without a restart.
+    #[cfg(feature = "sqlite")]
+    fn spawn_index_follower(&self, synced_etag: String) {
+        let records = self.clone();
+        tokio::spawn(async move {
+            let mut synced_etag = Some(synced_etag);
+            let mut interval = tokio::time::interval(FOLLOW_INDEX_INTERVAL);
+            // the first tick completes immediately, right after the sync on startup
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let etag = match s3::get_index_etag(&records.s3_client).await {
+                    Ok(etag) => etag,
+                    Err(error) => {
+                        tracing::error!(%error, "failed to check share index");
+                        continue;
+                    }
+                };
+                if etag.is_none() || etag == synced_etag {
+                    continue;
+                }
+                match records.follow_index().await {
+                    Ok(etag) => synced_etag = Some(etag),
+                    Err(error) => tracing::error!(%error, "failed to sync database with index"),
+                }
+            }
+        });
+    }
+}
@@ -2,7 +2,10 @@ use anyhow::Result;
 use aws_sdk_s3::{
     error::SdkError, operation::get_object::GetObjectError, primitives::ByteStream, Client,
 };
-use nanum_core::types::Metadata;
+use futures_util::{TryFutureExt, TryStreamExt};
+use http::{header, HeaderName, HeaderValue, StatusCode};
+use nanum_core::types::{ApiToken, Metadata, Revocations, ShareIndex};
+use serde::de::DeserializeOwned;
 
 use crate::config::CONFIG;
 
@@ -14,6 +17,13 @@ fn key_metadata(id: &str) -> String {
     format!("metadata/{id}.json")
 }
 
+fn key_token(id: &str) -> String {
+    format!("token/{id}.json")
+}
+
+const KEY_REVOCATIONS: &str = "revocations.json";
+const KEY_INDEX: &str = "index.json";
+
 async fn get_object(s3_client: &Client, key: &str) -> Result<Option<ByteStream>> {
     let resp = s3_client
         .get_object()
@@ -52,23 +62,411 @@ pub async fn get_metadata(s3_client: &Client, id: &str) -> Result<Option<Metadat
     }
 }
 
-pub async fn upload_metadata(s3_client: &Client, id: &str, metadata: &Metadata) -> Result<()> {
+/// Lists every share by fetching each metadata object.
+pub async fn list_metadatas(s3_client: &Client) -> Result<Vec<(String, Metadata)>> {
     s3_client
+        .list_objects_v2()
+        .bucket(&CONFIG.s3_bucket_name)
+        .prefix("metadata/")
+        .into_paginator()
+        .send()
+        .err_into::<anyhow::Error>()
+        .map_ok(|output| {
+            futures_util::stream::iter(
+                output
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Result::<_, anyhow::Error>::Ok),
+            )
+        })
+        .try_flatten()
+        .try_filter_map(|content| async move {
+            if let Some(key) = content.key() {
+                if let Some(id) = key
+                    .strip_prefix("metadata/")
+                    .and_then(|name| name.strip_suffix(".json"))
+                {
+                    if let Some(resp) = get_object(s3_client, key).await? {
+                        let body = resp.collect().await?.to_vec();
+                        return Ok(serde_json::from_slice::<Metadata>(&body)
+                            .ok()
+                            .map(|metadata| (id.to_string(), metadata)));
+                    }
+                }
+            }
+            Ok(None)
+        })
+        .try_collect()
+        .await
+}
+
+/// Fetches a JSON object along with its ETag, to be passed to a conditional write.
+async fn get_json_with_etag<T: DeserializeOwned>(
+    s3_client: &Client,
+    key: &str,
+) -> Result<Option<(T, String)>> {
+    let resp = s3_client
+        .get_object()
+        .bucket(&CONFIG.s3_bucket_name)
+        .key(key)
+        .send()
+        .await;
+    match resp {
+        Ok(resp) => {
+            let etag = resp
+                .e_tag()
+                .ok_or_else(|| anyhow::anyhow!("S3 did not return ETag of {key}"))?
+                .to_string();
+            let body = resp.body.collect().await?.to_vec();
+            Ok(Some((serde_json::from_slice(&body)?, etag)))
+        }
+        Err(SdkError::ServiceError(error))
+            if matches!(error.err(), GetObjectError::NoSuchKey(_)) =>
+        {
+            Ok(None)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Fetches metadata along with its ETag, to be passed to [`update_metadata`].
+pub async fn get_metadata_with_etag(
+    s3_client: &Client,
+    id: &str,
+) -> Result<Option<(Metadata, String)>> {
+    get_json_with_etag(s3_client, &key_metadata(id)).await
+}
+
+/// Puts an object only if `precondition` holds, using a conditional write.
+///
+/// Returns `false` if the precondition failed.
+async fn put_object_with_precondition(
+    s3_client: &Client,
+    key: &str,
+    body: ByteStream,
+    precondition: (HeaderName, HeaderValue),
+) -> Result<bool> {
+    let resp = s3_client
         .put_object()
         .bucket(&CONFIG.s3_bucket_name)
+        .key(key)
+        .body(body)
+        .customize()
+        .await?
+        .mutate_request(move |req| {
+            req.headers_mut().insert(precondition.0, precondition.1);
+        })
+        .send()
+        .await;
+    match resp {
+        Ok(_) => Ok(true),
+        // `If-Match` on a deleted object fails with 404
+        Err(SdkError::ServiceError(error))
+            if error.raw().http().status() == StatusCode::PRECONDITION_FAILED
+                || error.raw().http().status() == StatusCode::CONFLICT
+                || error.raw().http().status() == StatusCode::NOT_FOUND =>
+        {
+            Ok(false)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Puts an object only if the key does not exist yet.
+///
+/// Returns `false` if the object already exists.
+async fn put_object_if_absent(s3_client: &Client, key: &str, body: ByteStream) -> Result<bool> {
+    put_object_with_precondition(
+        s3_client,
+        key,
+        body,
+        (header::IF_NONE_MATCH, HeaderValue::from_static("*")),
+    )
+    .await
+}
+
+/// Creates metadata of a new share. Returns `false` if the ID is already taken.
+pub async fn create_metadata(s3_client: &Client, id: &str, metadata: &Metadata) -> Result<bool> {
+    put_object_if_absent(
+        s3_client,
+        &key_metadata(id),
+        serde_json::to_vec(metadata)?.into(),
+    )
+    .await
+}
+
+/// Overwrites metadata of a share. Returns `false` if it was modified since `etag` was fetched.
+pub async fn update_metadata(
+    s3_client: &Client,
+    id: &str,
+    metadata: &Metadata,
+    etag: &str,
+) -> Result<bool> {
+    put_object_with_precondition(
+        s3_client,
+        &key_metadata(id),
+        serde_json::to_vec(metadata)?.into(),
+        (header::IF_MATCH, HeaderValue::from_str(etag)?),
+    )
+    .await
+}
+
+/// Uploads a chunk. Returns `false` if the chunk is already uploaded.
+pub async fn create_file(s3_client: &Client, id: &str, seq: usize, data: Vec<u8>) -> Result<bool> {
+    put_object_if_absent(s3_client, &key_file(id, seq), data.into()).await
+}
+
+pub async fn delete_metadata(s3_client: &Client, id: &str) -> Result<()> {
+    s3_client
+        .delete_object()
+        .bucket(&CONFIG.s3_bucket_name)
         .key(key_metadata(id))
-        .body(serde_json::to_vec(metadata)?.into())
         .send()
         .await?;
     Ok(())
 }
 
-pub async fn upload_file(s3_client: &Client, id: &str, seq: usize, data: Vec<u8>) -> Result<()> {
+/// Deletes every chunk of a share.
+pub async fn delete_files(s3_client: &Client, id: &str) -> Result<()> {
     s3_client
-        .put_object()
+        .list_objects_v2()
+        .bucket(&CONFIG.s3_bucket_name)
+        .prefix(format!("file/{id}."))
+        .into_paginator()
+        .send()
+        .err_into::<anyhow::Error>()
+        .map_ok(|output| {
+            futures_util::stream::iter(
+                output
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Result::<_, anyhow::Error>::Ok),
+            )
+        })
+        .try_flatten()
+        .try_filter_map(|content| async move { Ok(content.key().map(str::to_string)) })
+        .and_then(|key| {
+            s3_client
+                .delete_object()
+                .bucket(&CONFIG.s3_bucket_name)
+                .key(key)
+                .send()
+                .err_into()
+        })
+        .map_ok(|_| ())
+        .try_collect::<()>()
+        .await
+}
+
+pub async fn get_index(s3_client: &Client) -> Result<Option<ShareIndex>> {
+    let resp = get_object(s3_client, KEY_INDEX).await?;
+    if let Some(resp) = resp {
+        let body = resp.collect().await?.to_vec();
+        Ok(Some(serde_json::from_slice(&body)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Fetches the index along with its ETag.
+#[cfg(feature = "sqlite")]
+pub async fn get_index_with_etag(s3_client: &Client) -> Result<Option<(ShareIndex, String)>> {
+    get_json_with_etag(s3_client, KEY_INDEX).await
+}
+
+/// ETag of the index, to tell whether it changed without fetching it.
+#[cfg(feature = "sqlite")]
+pub async fn get_index_etag(s3_client: &Client) -> Result<Option<String>> {
+    let resp = s3_client
+        .head_object()
+        .bucket(&CONFIG.s3_bucket_name)
+        .key(KEY_INDEX)
+        .send()
+        .await;
+    match resp {
+        Ok(resp) => Ok(resp.e_tag().map(str::to_string)),
+        Err(SdkError::ServiceError(error)) if error.err().is_not_found() => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Builds the index from every metadata, for when there is none yet.
+pub async fn build_index(s3_client: &Client) -> Result<ShareIndex> {
+    let shares = list_metadatas(s3_client)
+        .await?
+        .into_iter()
+        .map(|(id, metadata)| (id, metadata.index_entry()))
+        .collect();
+    Ok(ShareIndex { shares })
+}
+
+/// Builds and writes the index if there is none yet, so that requests never have to.
+pub async fn create_index_if_missing(s3_client: &Client) -> Result<()> {
+    if get_object(s3_client, KEY_INDEX).await?.is_some() {
+        return Ok(());
+    }
+    let index = build_index(s3_client).await?;
+    tracing::info!(count = index.shares.len(), "creating share index");
+    let created = put_object_with_precondition(
+        s3_client,
+        KEY_INDEX,
+        serde_json::to_vec(&index)?.into(),
+        (header::IF_NONE_MATCH, HeaderValue::from_static("*")),
+    )
+    .await?;
+    if !created {
+        tracing::info!("share index created concurrently");
+    }
+    Ok(())
+}
+
+/// Rebuilds the index from every metadata, to fix an index which missed updates.
+///
+/// Returns `false` if the index was modified while rebuilding it, since the update may be missing
+/// from the rebuilt one.
+pub async fn rebuild_index(s3_client: &Client) -> Result<bool> {
+    let precondition = match get_json_with_etag::<ShareIndex>(s3_client, KEY_INDEX).await? {
+        Some((_, etag)) => (header::IF_MATCH, HeaderValue::from_str(&etag)?),
+        None => (header::IF_NONE_MATCH, HeaderValue::from_static("*")),
+    };
+    let index = build_index(s3_client).await?;
+    put_object_with_precondition(
+        s3_client,
+        KEY_INDEX,
+        serde_json::to_vec(&index)?.into(),
+        precondition,
+    )
+    .await
+}
+
+/// The index is written on every upload, so a lost conditional write is retried.
+const MAX_INDEX_UPDATE_ATTEMPTS: usize = 5;
+
+/// Longest random wait before retrying a lost conditional write of the index, multiplied by the
+/// number of attempts so far. Spreads out writers which lost to each other.
+const INDEX_UPDATE_BACKOFF_MILLIS: u64 = 100;
+
+/// Applies `update` to the index with a conditional write. The index is created at startup, so a
+/// missing one has to be rebuilt with `nanum-admin reindex`.
+pub async fn update_index(s3_client: &Client, update: impl Fn(&mut ShareIndex)) -> Result<()> {
+    for attempt in 0..MAX_INDEX_UPDATE_ATTEMPTS {
+        if attempt > 0 {
+            let millis = rand::random::<u64>() % (INDEX_UPDATE_BACKOFF_MILLIS * attempt as u64);
+            tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+        }
+        let (mut index, etag): (ShareIndex, _) = get_json_with_etag(s3_client, KEY_INDEX)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("share index is missing, run `nanum-admin reindex`"))?;
+        let precondition = (header::IF_MATCH, HeaderValue::from_str(&etag)?);
+
+        update(&mut index);
+
+        let updated = put_object_with_precondition(
+            s3_client,
+            KEY_INDEX,
+            serde_json::to_vec(&index)?.into(),
+            precondition,
+        )
+        .await?;
+        if updated {
+            return Ok(());
+        }
+        tracing::warn!("share index modified concurrently");
+    }
+    anyhow::bail!("share index modified concurrently")
+}
+
+pub async fn get_revocations(s3_client: &Client) -> Result<Revocations> {
+    let resp = get_object(s3_client, KEY_REVOCATIONS).await?;
+    if let Some(resp) = resp {
+        let body = resp.collect().await?.to_vec();
+        let revocations = serde_json::from_slice(&body)?;
+        Ok(revocations)
+    } else {
+        Ok(Revocations::default())
+    }
+}
+
+pub async fn get_token(s3_client: &Client, id: &str) -> Result<Option<ApiToken>> {
+    let resp = get_object(s3_client, &key_token(id)).await?;
+    if let Some(resp) = resp {
+        let body = resp.collect().await?.to_vec();
+        let token = serde_json::from_slice(&body)?;
+        Ok(Some(token))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Fetches an API token along with its ETag, to be passed to [`update_token`].
+pub async fn get_token_with_etag(
+    s3_client: &Client,
+    id: &str,
+) -> Result<Option<(ApiToken, String)>> {
+    get_json_with_etag(s3_client, &key_token(id)).await
+}
+
+pub async fn list_tokens(s3_client: &Client) -> Result<Vec<ApiToken>> {
+    s3_client
+        .list_objects_v2()
+        .bucket(&CONFIG.s3_bucket_name)
+        .prefix("token/")
+        .into_paginator()
+        .send()
+        .err_into::<anyhow::Error>()
+        .map_ok(|output| {
+            futures_util::stream::iter(
+                output
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Result::<_, anyhow::Error>::Ok),
+            )
+        })
+        .try_flatten()
+        .try_filter_map(|content| async move {
+            if let Some(key) = content.key() {
+                if let Some(resp) = get_object(s3_client, key).await? {
+                    let body = resp.collect().await?.to_vec();
+                    return Ok(serde_json::from_slice::<ApiToken>(&body).ok());
+                }
+            }
+            Ok(None)
+        })
+        .try_collect()
+        .await
+}
+
+/// Uploads a new API token. Returns `false` if a token with the same ID exists.
+pub async fn create_token(s3_client: &Client, token: &ApiToken) -> Result<bool> {
+    put_object_if_absent(
+        s3_client,
+        &key_token(&token.id),
+        serde_json::to_vec(token)?.into(),
+    )
+    .await
+}
+
+/// Overwrites an API token only if it is unchanged since it was fetched with `etag`.
+///
+/// Returns `false` if it was modified or deleted in the meantime.
+pub async fn update_token(s3_client: &Client, token: &ApiToken, etag: &str) -> Result<bool> {
+    put_object_with_precondition(
+        s3_client,
+        &key_token(&token.id),
+        serde_json::to_vec(token)?.into(),
+        (header::IF_MATCH, HeaderValue::from_str(etag)?),
+    )
+    .await
+}
+
+pub async fn delete_token(s3_client: &Client, id: &str) -> Result<()> {
+    s3_client
+        .delete_object()
         .bucket(&CONFIG.s3_bucket_name)
-        .key(key_file(id, seq))
-        .body(data.into())
+        .key(key_token(id))
         .send()
         .await?;
     Ok(())
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// Machine-readable error codes returned by the API.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorCode {
+    BadRequest,
+    Unauthorized,
+    /// The signed in account is not allowed to use the server.
+    UserNotAllowed,
+    Forbidden,
+    NotFound,
+    InvalidId,
+    IdTaken,
+    ChunkExists,
+    AlreadyCompleted,
+    PayloadTooLarge,
+    TooManyRequests,
+    /// The share is disabled by an administrator.
+    Disabled,
+    Internal,
+    /// Code unknown to this version of `nanum-core`.
+    #[serde(other)]
+    Unknown,
+}
+
+impl ApiErrorCode {
+    pub fn status_code(self) -> u16 {
+        match self {
+            Self::BadRequest | Self::InvalidId => 400,
+            Self::Unauthorized => 401,
+            Self::UserNotAllowed | Self::Forbidden | Self::Disabled => 403,
+            Self::NotFound => 404,
+            Self::IdTaken | Self::ChunkExists | Self::AlreadyCompleted => 409,
+            Self::PayloadTooLarge => 413,
+            Self::TooManyRequests => 429,
+            Self::Internal | Self::Unknown => 500,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiError {
+    pub code: ApiErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ApiErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
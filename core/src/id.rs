@@ -0,0 +1,89 @@
+use std::fmt;
+
+pub const MAX_ID_LENGTH: usize = 64;
+
+/// IDs which collide with routes of the backend or the web UI.
+pub const RESERVED_IDS: &[&str] = &["api", "auth", "shares", "static", "tokens"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IdError {
+    Empty,
+    TooLong,
+    InvalidCharacter,
+    Reserved,
+}
+
+impl IdError {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Empty => "ID must not be empty",
+            Self::TooLong => "ID must be at most 64 characters long",
+            Self::InvalidCharacter => "ID may only contain letters, digits, '-' and '_'",
+            Self::Reserved => "ID is reserved",
+        }
+    }
+}
+
+impl fmt::Display for IdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for IdError {}
+
+pub fn is_valid_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Validates a share ID, which is used both as a URL path segment and as a part of S3 keys.
+pub fn validate(id: &str) -> Result<(), IdError> {
+    if id.is_empty() {
+        Err(IdError::Empty)
+    } else if id.len() > MAX_ID_LENGTH {
+        Err(IdError::TooLong)
+    } else if !id.chars().all(is_valid_id_char) {
+        Err(IdError::InvalidCharacter)
+    } else if RESERVED_IDS
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(id))
+    {
+        Err(IdError::Reserved)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_ids() {
+        assert_eq!(validate("a"), Ok(()));
+        assert_eq!(validate("My-file_2"), Ok(()));
+        assert_eq!(validate(&"a".repeat(MAX_ID_LENGTH)), Ok(()));
+        // only whole reserved names are rejected
+        assert_eq!(validate("apis"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_invalid_ids() {
+        assert_eq!(validate(""), Err(IdError::Empty));
+        assert_eq!(
+            validate(&"a".repeat(MAX_ID_LENGTH + 1)),
+            Err(IdError::TooLong)
+        );
+        for id in ["a/b", "..", "a.json", "a b", "파일", "a%2F"] {
+            assert_eq!(validate(id), Err(IdError::InvalidCharacter), "{id}");
+        }
+    }
+
+    #[test]
+    fn rejects_reserved_ids_in_any_case() {
+        for id in RESERVED_IDS {
+            assert_eq!(validate(id), Err(IdError::Reserved));
+            assert_eq!(validate(&id.to_uppercase()), Err(IdError::Reserved));
+        }
+    }
+}
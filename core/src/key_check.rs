@@ -0,0 +1,59 @@
+//! Key check values, which tell a wrong passphrase or private key apart from corrupted data
+//! before any chunk is downloaded.
+//!
+//! The value is derived from the same salt and secret as the encryption key but with a different
+//! HKDF info, so it reveals nothing about the encryption key.
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+pub const KEY_CHECK_LENGTH: usize = 32;
+
+const INFO: &[u8] = b"nanum/v1/key-check";
+
+/// Derives the key check value of the encryption key derived from `salt` and `secret`.
+pub fn derive(salt: &[u8], secret: &[u8]) -> [u8; KEY_CHECK_LENGTH] {
+    let mut check = [0u8; KEY_CHECK_LENGTH];
+    Hkdf::<Sha256>::new(Some(salt), secret)
+        .expand(INFO, &mut check)
+        .expect("32 bytes is a valid HKDF output length");
+    check
+}
+
+/// Whether `secret` matches the key check value `check`.
+pub fn verify(salt: &[u8], secret: &[u8], check: &[u8]) -> bool {
+    derive(salt, secret).as_slice() == check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT: [u8; 32] = [1; 32];
+    const SECRET: &[u8] = b"correct horse battery staple";
+
+    #[test]
+    fn verifies_matching_secret() {
+        let check = derive(&SALT, SECRET);
+        assert!(verify(&SALT, SECRET, &check));
+    }
+
+    #[test]
+    fn rejects_wrong_secret_salt_or_check() {
+        let check = derive(&SALT, SECRET);
+        assert!(!verify(&SALT, b"correct horse battery stapler", &check));
+        assert!(!verify(&[2; 32], SECRET, &check));
+        assert!(!verify(&SALT, SECRET, &check[1..]));
+        assert!(!verify(&SALT, SECRET, &[]));
+    }
+
+    #[test]
+    fn differs_from_encryption_key() {
+        // the encryption key is expanded from the same input with an empty info
+        let mut key = [0u8; KEY_CHECK_LENGTH];
+        Hkdf::<Sha256>::new(Some(&SALT), SECRET)
+            .expand(&[], &mut key)
+            .unwrap();
+        assert_ne!(derive(&SALT, SECRET), key);
+    }
+}
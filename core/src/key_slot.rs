@@ -0,0 +1,109 @@
+//! Key slots, which wrap the file key of a share under several passphrases, similar to LUKS.
+//!
+//! Each recipient can be handed a different passphrase, and a slot can be removed later without
+//! re-encrypting the file.
+
+use chacha20poly1305::{aead::Aead, Key, KeyInit, XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use crate::types::KeySlot;
+
+pub const SALT_LENGTH: usize = 32;
+pub const NONCE_LENGTH: usize = 24;
+/// Maximum number of key slots of a single share.
+pub const MAX_KEY_SLOTS: usize = 16;
+pub const MAX_NAME_LENGTH: usize = 64;
+
+fn derive_wrap_key(salt: &[u8], passphrase: &str) -> Key {
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(salt), passphrase.as_bytes())
+        .expand(&[], &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    key
+}
+
+/// Wraps `file_key` under `passphrase` into a new slot.
+pub fn wrap(name: String, passphrase: &str, file_key: &[u8]) -> Result<KeySlot, getrandom::Error> {
+    let mut salt = [0u8; SALT_LENGTH];
+    getrandom::getrandom(&mut salt)?;
+    let mut nonce = [0u8; NONCE_LENGTH];
+    getrandom::getrandom(&mut nonce)?;
+
+    let wrapped_key = XChaCha20Poly1305::new(&derive_wrap_key(&salt, passphrase))
+        .encrypt(XNonce::from_slice(&nonce), file_key)
+        .expect("encrypting in memory never fails");
+
+    Ok(KeySlot {
+        name,
+        salt: salt.to_vec(),
+        nonce: nonce.to_vec(),
+        wrapped_key,
+    })
+}
+
+/// Recovers the file key if `slot` was wrapped under `passphrase`.
+pub fn unwrap(slot: &KeySlot, passphrase: &str) -> Option<Vec<u8>> {
+    if slot.nonce.len() != NONCE_LENGTH {
+        return None;
+    }
+    XChaCha20Poly1305::new(&derive_wrap_key(&slot.salt, passphrase))
+        .decrypt(XNonce::from_slice(&slot.nonce), slot.wrapped_key.as_slice())
+        .ok()
+}
+
+/// Recovers the file key from the first of `slots` which was wrapped under `passphrase`.
+pub fn unwrap_any(slots: &[KeySlot], passphrase: &str) -> Option<Vec<u8>> {
+    slots.iter().find_map(|slot| unwrap(slot, passphrase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn slot_round_trips() {
+        let slot = wrap("alice".to_string(), "correct horse", &FILE_KEY).unwrap();
+        assert_eq!(slot.name, "alice");
+        assert_eq!(unwrap(&slot, "correct horse").unwrap(), FILE_KEY);
+    }
+
+    #[test]
+    fn unwrap_any_finds_matching_slot() {
+        let slots = [
+            wrap("alice".to_string(), "first", &FILE_KEY).unwrap(),
+            wrap("bob".to_string(), "second", &FILE_KEY).unwrap(),
+        ];
+        assert_eq!(unwrap_any(&slots, "second").unwrap(), FILE_KEY);
+        assert!(unwrap_any(&slots, "third").is_none());
+        assert!(unwrap_any(&[], "first").is_none());
+    }
+
+    #[test]
+    fn slots_are_salted() {
+        let first = wrap("a".to_string(), "same", &FILE_KEY).unwrap();
+        let second = wrap("b".to_string(), "same", &FILE_KEY).unwrap();
+        assert_ne!(first.salt, second.salt);
+        assert_ne!(first.wrapped_key, second.wrapped_key);
+    }
+
+    #[test]
+    fn unwrap_rejects_wrong_passphrase_and_tampering() {
+        let slot = wrap("alice".to_string(), "correct horse", &FILE_KEY).unwrap();
+        assert!(unwrap(&slot, "Correct horse").is_none());
+
+        let mut tampered = slot.clone();
+        tampered.wrapped_key[0] ^= 1;
+        assert!(unwrap(&tampered, "correct horse").is_none());
+
+        let mut tampered = slot.clone();
+        tampered.salt[0] ^= 1;
+        assert!(unwrap(&tampered, "correct horse").is_none());
+
+        let mut tampered = slot;
+        tampered.nonce.pop();
+        assert!(unwrap(&tampered, "correct horse").is_none());
+    }
+}
@@ -1,2 +1,10 @@
+pub mod error;
+pub mod id;
+pub mod key_check;
+pub mod key_slot;
+pub mod padding;
+pub mod passphrase;
+pub mod recipient;
+pub mod token;
 pub mod types;
 pub mod utils;
@@ -0,0 +1,77 @@
+//! Padding which hides the exact size of a file from anyone without the key.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PaddingScheme {
+    /// [Padmé](https://lbarman.ch/blog/padme/), which leaks at most `O(log log L)` bits of the
+    /// size `L` with at most 12% of overhead.
+    Padme,
+}
+
+impl PaddingScheme {
+    /// Size of the plaintext after padding `len` bytes.
+    pub fn padded_len(self, len: usize) -> usize {
+        match self {
+            Self::Padme => padme(len),
+        }
+    }
+}
+
+fn padme(len: usize) -> usize {
+    if len < 2 {
+        return len;
+    }
+    // floor(log2(len))
+    let e = usize::BITS - 1 - len.leading_zeros();
+    // floor(log2(e)) + 1
+    let s = u32::BITS - e.leading_zeros();
+    let mask = (1usize << (e - s)) - 1;
+    (len + mask) & !mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_to_known_sizes() {
+        for (len, padded) in [
+            (0, 0),
+            (1, 1),
+            (2, 2),
+            (9, 10),
+            (100, 104),
+            (1000, 1024),
+            (1025, 1088),
+            (1_000_000, 1_015_808),
+        ] {
+            assert_eq!(PaddingScheme::Padme.padded_len(len), padded, "{len}");
+        }
+    }
+
+    #[test]
+    fn padding_is_bounded_and_monotonic() {
+        let mut previous = 0;
+        for len in 0..100_000 {
+            let padded = PaddingScheme::Padme.padded_len(len);
+            assert!(padded >= previous, "{len}");
+            previous = padded;
+        }
+        for len in (0..100_000).chain((1..64).map(|shift| (1usize << shift) + 1)) {
+            let padded = PaddingScheme::Padme.padded_len(len);
+            assert!(padded >= len, "{len}");
+            // Padmé adds at most 12% of overhead
+            assert!((padded - len) as f64 <= len as f64 * 0.12, "{len}");
+        }
+    }
+
+    #[test]
+    fn padded_sizes_are_fixed_points() {
+        for len in [3, 77, 4097, 123_456_789] {
+            let padded = PaddingScheme::Padme.padded_len(len);
+            assert_eq!(PaddingScheme::Padme.padded_len(padded), padded);
+        }
+    }
+}
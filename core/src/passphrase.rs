@@ -0,0 +1,177 @@
+//! Strength estimation and generation of passphrases.
+//!
+//! Passphrases never leave the browser, so the strength policy can only be applied by clients.
+
+const WORDLIST: &str = include_str!("../res/eff_large_wordlist.txt");
+const WORDLIST_LEN: usize = 7776;
+
+/// Words of a generated passphrase. 5 words are about 64 bits.
+pub const DEFAULT_WORD_COUNT: usize = 5;
+
+/// Passwords which are guessed first no matter how long they are.
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "1234567",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "111111",
+    "000000",
+    "abc123",
+    "admin",
+    "changeme",
+    "dragon",
+    "football",
+    "iloveyou",
+    "letmein",
+    "monkey",
+    "passw0rd",
+    "password",
+    "password1",
+    "qwerty",
+    "qwerty123",
+    "secret",
+    "sunshine",
+    "welcome",
+];
+
+const KEYBOARD_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+fn words() -> impl Iterator<Item = &'static str> {
+    WORDLIST.lines().filter(|line| !line.starts_with('#'))
+}
+
+fn is_word(token: &str) -> bool {
+    words().any(|word| word == token)
+}
+
+/// Whether `b` follows `a` in the alphabet, in digits or on a keyboard row, in either direction.
+fn is_sequential(a: char, b: char) -> bool {
+    if (a as u32).abs_diff(b as u32) == 1 {
+        return true;
+    }
+    if !a.is_ascii() || !b.is_ascii() {
+        return false;
+    }
+    KEYBOARD_ROWS.iter().any(|row| {
+        row.as_bytes()
+            .windows(2)
+            .any(|pair| pair == [a as u8, b as u8] || pair == [b as u8, a as u8])
+    })
+}
+
+/// Size of the smallest set of characters the passphrase could have been drawn from.
+fn charset_size(passphrase: &str) -> f64 {
+    let mut size = 0;
+    if passphrase.chars().any(|c| c.is_ascii_lowercase()) {
+        size += 26;
+    }
+    if passphrase.chars().any(|c| c.is_ascii_uppercase()) {
+        size += 26;
+    }
+    if passphrase.chars().any(|c| c.is_ascii_digit()) {
+        size += 10;
+    }
+    if passphrase
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        size += 33;
+    }
+    if !passphrase.is_ascii() {
+        size += 100;
+    }
+    size as f64
+}
+
+/// Entropy of a token made of random characters, where repeated and sequential characters like
+/// `aaaa` or `1234` barely count.
+fn characters_entropy(token: &str, bits_per_char: f64) -> f64 {
+    let lower = token.to_lowercase();
+    let mut entropy = 0.;
+    let mut prev = None;
+    for c in lower.chars() {
+        entropy += match prev {
+            Some(prev) if prev == c || is_sequential(prev, c) => 1.,
+            _ => bits_per_char,
+        };
+        prev = Some(c);
+    }
+    entropy
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '@' | '4' => 'a',
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '5' | '$' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+/// Entropy of a token which is a common password or a word of the wordlist, allowing leet
+/// substitutions like `p@ssw0rd` and a suffix like `password123!`.
+fn dictionary_entropy(token: &str, bits_per_char: f64) -> Option<f64> {
+    let stem = token.trim_end_matches(|c: char| !c.is_alphabetic());
+    if stem.is_empty() {
+        return None;
+    }
+    let suffix = &token[stem.len()..];
+    let unleeted = stem.chars().map(unleet).collect::<String>();
+
+    let base = if COMMON_PASSWORDS.contains(&unleeted.as_str()) {
+        (COMMON_PASSWORDS.len() as f64).log2()
+    } else if is_word(&unleeted) {
+        (WORDLIST_LEN as f64).log2()
+    } else {
+        return None;
+    };
+    let substitutions = stem.chars().filter(|c| unleet(*c) != *c).count() as f64;
+    Some(base + substitutions + characters_entropy(suffix, bits_per_char))
+}
+
+/// Roughly estimates the entropy of a passphrase in bits, in the spirit of zxcvbn.
+///
+/// Common passwords and words of the diceware wordlist count as a single guess out of their
+/// list instead of as random characters.
+pub fn estimate_entropy(passphrase: &str) -> f64 {
+    if passphrase.is_empty() {
+        return 0.;
+    }
+    let lower = passphrase.to_lowercase();
+    let bits_per_char = charset_size(passphrase).log2();
+    let tokens = lower
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_' || c == '.')
+        .filter(|token| !token.is_empty())
+        .collect::<Vec<_>>();
+    let separators = tokens.len().saturating_sub(1) as f64;
+
+    tokens
+        .iter()
+        .map(|token| {
+            dictionary_entropy(token, bits_per_char)
+                .unwrap_or_else(|| characters_entropy(token, bits_per_char))
+        })
+        .sum::<f64>()
+        + separators
+}
+
+/// Generates a diceware passphrase of `word_count` words from the EFF long wordlist.
+pub fn generate(word_count: usize) -> Result<String, getrandom::Error> {
+    let words = words().collect::<Vec<_>>();
+    // Reject values from the incomplete range at the top to avoid modulo bias.
+    let zone = u32::MAX - u32::MAX % words.len() as u32;
+    let mut chosen = Vec::with_capacity(word_count);
+    while chosen.len() < word_count {
+        let mut bytes = [0u8; 4];
+        getrandom::getrandom(&mut bytes)?;
+        let value = u32::from_le_bytes(bytes);
+        if value < zone {
+            chosen.push(words[value as usize % words.len()]);
+        }
+    }
+    Ok(chosen.join(" "))
+}
@@ -0,0 +1,289 @@
+//! X25519 recipients, which let an uploader encrypt the file key to public keys instead of a
+//! shared passphrase.
+//!
+//! Keys use the same Bech32 encoding as [age](https://age-encryption.org), so keys generated by
+//! `age-keygen` can be used as well.
+
+use std::{fmt, str::FromStr};
+
+use bech32::{FromBase32, ToBase32, Variant};
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key, KeyInit, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::types::WrappedKey;
+
+const RECIPIENT_HRP: &str = "age";
+const IDENTITY_HRP: &str = "age-secret-key-";
+const WRAP_KEY_INFO: &[u8] = b"nanum/v1/X25519";
+
+/// Length of the file key wrapped to each recipient.
+pub const FILE_KEY_LENGTH: usize = 32;
+/// Maximum number of recipients of a single share.
+pub const MAX_RECIPIENTS: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyError {
+    InvalidEncoding,
+    InvalidPrefix,
+    InvalidLength,
+}
+
+impl KeyError {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::InvalidEncoding => "key is not a valid Bech32 string",
+            Self::InvalidPrefix => "key has an unexpected prefix",
+            Self::InvalidLength => "key has an unexpected length",
+        }
+    }
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+fn decode_key(s: &str, hrp: &str) -> Result<[u8; 32], KeyError> {
+    let (decoded_hrp, data, variant) =
+        bech32::decode(s.trim()).map_err(|_| KeyError::InvalidEncoding)?;
+    if decoded_hrp != hrp || variant != Variant::Bech32 {
+        return Err(KeyError::InvalidPrefix);
+    }
+    let bytes = Vec::<u8>::from_base32(&data).map_err(|_| KeyError::InvalidEncoding)?;
+    bytes.try_into().map_err(|_| KeyError::InvalidLength)
+}
+
+fn encode_key(bytes: &[u8], hrp: &str) -> String {
+    bech32::encode(hrp, bytes.to_base32(), Variant::Bech32).expect("HRP is valid")
+}
+
+fn derive_wrap_key(shared_secret: &[u8], ephemeral: &PublicKey, recipient: &PublicKey) -> Key {
+    let mut salt = [0u8; 64];
+    salt[..32].copy_from_slice(ephemeral.as_bytes());
+    salt[32..].copy_from_slice(recipient.as_bytes());
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+        .expand(WRAP_KEY_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    key
+}
+
+/// Public key which file keys are wrapped to, encoded as `age1...`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Recipient(PublicKey);
+
+impl Recipient {
+    /// Encrypts `file_key` so that only the holder of the matching [`Identity`] can recover it.
+    pub fn wrap(&self, file_key: &[u8]) -> Result<WrappedKey, getrandom::Error> {
+        let mut ephemeral_secret = [0u8; 32];
+        getrandom::getrandom(&mut ephemeral_secret)?;
+        let ephemeral_secret = StaticSecret::from(ephemeral_secret);
+        let ephemeral_public = PublicKey::from(&ephemeral_secret);
+
+        let shared_secret = ephemeral_secret.diffie_hellman(&self.0);
+        let wrap_key = derive_wrap_key(shared_secret.as_bytes(), &ephemeral_public, &self.0);
+        // Every wrap key is used exactly once, so a zero nonce is fine.
+        let wrapped_key = ChaCha20Poly1305::new(&wrap_key)
+            .encrypt(&Nonce::default(), file_key)
+            .expect("encrypting in memory never fails");
+
+        Ok(WrappedKey {
+            ephemeral_public: ephemeral_public.as_bytes().to_vec(),
+            wrapped_key,
+        })
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        decode_key(s, RECIPIENT_HRP).map(|bytes| Self(PublicKey::from(bytes)))
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&encode_key(self.0.as_bytes(), RECIPIENT_HRP))
+    }
+}
+
+/// Private key of a recipient, encoded as `AGE-SECRET-KEY-1...`.
+#[derive(Clone)]
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    pub fn generate() -> Result<Self, getrandom::Error> {
+        let mut secret = [0u8; 32];
+        getrandom::getrandom(&mut secret)?;
+        Ok(Self(StaticSecret::from(secret)))
+    }
+
+    pub fn to_public(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+
+    /// Recovers the file key if `wrapped` was wrapped to this identity.
+    pub fn unwrap(&self, wrapped: &WrappedKey) -> Option<Vec<u8>> {
+        let ephemeral_public: [u8; 32] = wrapped.ephemeral_public.as_slice().try_into().ok()?;
+        let ephemeral_public = PublicKey::from(ephemeral_public);
+
+        let shared_secret = self.0.diffie_hellman(&ephemeral_public);
+        if !shared_secret.was_contributory() {
+            return None;
+        }
+        let wrap_key = derive_wrap_key(
+            shared_secret.as_bytes(),
+            &ephemeral_public,
+            &PublicKey::from(&self.0),
+        );
+        ChaCha20Poly1305::new(&wrap_key)
+            .decrypt(&Nonce::default(), wrapped.wrapped_key.as_slice())
+            .ok()
+    }
+
+    /// Recovers the file key from the first of `wrapped_keys` which was wrapped to this identity.
+    pub fn unwrap_any(&self, wrapped_keys: &[WrappedKey]) -> Option<Vec<u8>> {
+        wrapped_keys.iter().find_map(|wrapped| self.unwrap(wrapped))
+    }
+}
+
+impl FromStr for Identity {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        decode_key(s, IDENTITY_HRP).map(|bytes| Self(StaticSecret::from(bytes)))
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&encode_key(self.0.as_bytes(), IDENTITY_HRP).to_uppercase())
+    }
+}
+
+/// Parses recipients separated by whitespace, ignoring `#` comments like age recipient files.
+pub fn parse_recipients(s: &str) -> Result<Vec<Recipient>, KeyError> {
+    s.lines()
+        .map(|line| line.split_once('#').map_or(line, |(line, _)| line))
+        .flat_map(str::split_whitespace)
+        .map(str::parse)
+        .collect()
+}
+
+/// Parses the first identity in `s`, skipping `#` comments like age identity files.
+pub fn parse_identity(s: &str) -> Result<Identity, KeyError> {
+    s.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .ok_or(KeyError::InvalidEncoding)?
+        .parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_KEY: [u8; FILE_KEY_LENGTH] = [7; FILE_KEY_LENGTH];
+
+    #[test]
+    fn wrapped_key_round_trips() {
+        let identity = Identity::generate().unwrap();
+        let wrapped = identity.to_public().wrap(&FILE_KEY).unwrap();
+        assert_eq!(identity.unwrap(&wrapped).unwrap(), FILE_KEY);
+    }
+
+    #[test]
+    fn unwrap_any_finds_own_key() {
+        let identity = Identity::generate().unwrap();
+        let other = Identity::generate().unwrap();
+        let wrapped_keys = [
+            other.to_public().wrap(&FILE_KEY).unwrap(),
+            identity.to_public().wrap(&FILE_KEY).unwrap(),
+        ];
+        assert_eq!(identity.unwrap_any(&wrapped_keys).unwrap(), FILE_KEY);
+    }
+
+    #[test]
+    fn unwrap_rejects_other_identity() {
+        let wrapped = Identity::generate()
+            .unwrap()
+            .to_public()
+            .wrap(&FILE_KEY)
+            .unwrap();
+        assert!(Identity::generate().unwrap().unwrap(&wrapped).is_none());
+    }
+
+    #[test]
+    fn unwrap_rejects_tampered_keys() {
+        let identity = Identity::generate().unwrap();
+        let wrapped = identity.to_public().wrap(&FILE_KEY).unwrap();
+
+        let mut tampered = wrapped.clone();
+        tampered.wrapped_key[0] ^= 1;
+        assert!(identity.unwrap(&tampered).is_none());
+
+        let mut tampered = wrapped.clone();
+        tampered.ephemeral_public[0] ^= 1;
+        assert!(identity.unwrap(&tampered).is_none());
+
+        // the all-zero point gives an all-zero shared secret
+        let mut tampered = wrapped;
+        tampered.ephemeral_public = vec![0; 32];
+        assert!(identity.unwrap(&tampered).is_none());
+    }
+
+    #[test]
+    fn keys_round_trip_through_encoding() {
+        let identity = Identity::generate().unwrap();
+        let recipient = identity.to_public();
+
+        let encoded = recipient.to_string();
+        assert!(encoded.starts_with("age1"));
+        assert!(encoded.parse::<Recipient>().unwrap() == recipient);
+
+        let encoded = identity.to_string();
+        assert!(encoded.starts_with("AGE-SECRET-KEY-1"));
+        let parsed = parse_identity(&format!("# comment\n\n{encoded}\n")).unwrap();
+        assert!(parsed.to_public() == recipient);
+    }
+
+    #[test]
+    fn parses_recipient_lists() {
+        let first = Identity::generate().unwrap().to_public();
+        let second = Identity::generate().unwrap().to_public();
+        let parsed = parse_recipients(&format!("# team\n{first} {second} # both\n\n")).unwrap();
+        assert!(parsed == vec![first, second]);
+    }
+
+    #[test]
+    fn rejects_malformed_keys() {
+        let identity = Identity::generate().unwrap();
+        assert_eq!(
+            identity.to_string().parse::<Recipient>().err(),
+            Some(KeyError::InvalidPrefix)
+        );
+        assert_eq!(
+            identity.to_public().to_string().parse::<Identity>().err(),
+            Some(KeyError::InvalidPrefix)
+        );
+        assert_eq!(
+            "age1invalid".parse::<Recipient>().err(),
+            Some(KeyError::InvalidEncoding)
+        );
+        let short = encode_key(&[1; 16], RECIPIENT_HRP);
+        assert_eq!(
+            short.parse::<Recipient>().err(),
+            Some(KeyError::InvalidLength)
+        );
+        assert_eq!(
+            parse_identity("# only a comment").err(),
+            Some(KeyError::InvalidEncoding)
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+const TOKEN_PREFIX: &str = "nanum_";
+const TOKEN_ID_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+const TOKEN_ID_LENGTH: usize = 12;
+const TOKEN_SECRET_LENGTH: usize = 32;
+
+pub struct GeneratedToken {
+    pub id: String,
+    pub secret_hash: Vec<u8>,
+    /// Full token handed to the user. Only its hash is stored.
+    pub token: String,
+}
+
+pub fn generate() -> Result<GeneratedToken, getrandom::Error> {
+    // Reject bytes from the incomplete range at the top to avoid modulo bias.
+    let zone = u8::MAX - (u8::MAX % TOKEN_ID_ALPHABET.len() as u8);
+    let mut id = String::with_capacity(TOKEN_ID_LENGTH);
+    while id.len() < TOKEN_ID_LENGTH {
+        let mut bytes = [0u8; TOKEN_ID_LENGTH];
+        getrandom::getrandom(&mut bytes)?;
+        id.extend(
+            bytes
+                .iter()
+                .filter(|b| **b < zone)
+                .map(|b| TOKEN_ID_ALPHABET[*b as usize % TOKEN_ID_ALPHABET.len()] as char)
+                .take(TOKEN_ID_LENGTH - id.len()),
+        );
+    }
+
+    let mut secret = [0u8; TOKEN_SECRET_LENGTH];
+    getrandom::getrandom(&mut secret)?;
+    let secret = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(secret);
+
+    Ok(GeneratedToken {
+        secret_hash: hash_secret(&secret),
+        token: format!("{TOKEN_PREFIX}{id}_{secret}"),
+        id,
+    })
+}
+
+/// Splits a token into its ID and secret. The ID is checked, since it is a part of an S3 key.
+pub fn parse(token: &str) -> Option<(&str, &str)> {
+    let (id, secret) = token.strip_prefix(TOKEN_PREFIX)?.split_once('_')?;
+    if id.len() != TOKEN_ID_LENGTH || !id.bytes().all(|b| TOKEN_ID_ALPHABET.contains(&b)) {
+        return None;
+    }
+    if secret.is_empty() {
+        return None;
+    }
+    Some((id, secret))
+}
+
+pub fn hash_secret(secret: &str) -> Vec<u8> {
+    Sha256::digest(secret.as_bytes()).to_vec()
+}
+
+/// Checks `secret` against a stored hash in constant time.
+pub fn verify(secret: &str, secret_hash: &[u8]) -> bool {
+    let hash = hash_secret(secret);
+    hash.len() == secret_hash.len()
+        && hash
+            .iter()
+            .zip(secret_hash)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_token_round_trips() {
+        let generated = generate().unwrap();
+        assert_eq!(generated.id.len(), TOKEN_ID_LENGTH);
+        assert!(generated.id.bytes().all(|b| TOKEN_ID_ALPHABET.contains(&b)));
+
+        let (id, secret) = parse(&generated.token).unwrap();
+        assert_eq!(id, generated.id);
+        assert!(verify(secret, &generated.secret_hash));
+    }
+
+    #[test]
+    fn secret_may_contain_separator() {
+        assert_eq!(
+            parse("nanum_abcdefghij12_se_cr_et"),
+            Some(("abcdefghij12", "se_cr_et"))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_tokens() {
+        assert_eq!(parse("abcdefghij12_secret"), None);
+        assert_eq!(parse("nanum_abcdefghij12"), None);
+        assert_eq!(parse("nanum_abcdefghij12_"), None);
+        assert_eq!(parse("nanum_short_secret"), None);
+        assert_eq!(parse("nanum_ABCDEFGHIJ12_secret"), None);
+        assert_eq!(parse("nanum_../../abcdef_secret"), None);
+    }
+
+    #[test]
+    fn verify_rejects_wrong_secret() {
+        let generated = generate().unwrap();
+        let (_, secret) = parse(&generated.token).unwrap();
+        assert!(!verify(&format!("{secret}x"), &generated.secret_hash));
+        assert!(!verify(secret, &generated.secret_hash[1..]));
+        assert!(!verify(secret, &[]));
+    }
+}
@@ -1,52 +1,469 @@
+use std::collections::{BTreeMap, HashMap};
+
 use serde::{Deserialize, Serialize};
 
+use crate::padding::PaddingScheme;
+
+/// Stored record of a share. Only the backend and administrators see it as is. Downloaders get
+/// [`ShareInfo`] instead.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Metadata {
     pub creator_email: String,
     #[serde(with = "crate::utils::base64")]
     pub salt: Vec<u8>,
+    /// See [`crate::key_check`]. Absent for shares uploaded before key checks.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::base64::option"
+    )]
+    pub key_check: Option<Vec<u8>>,
     #[serde(with = "crate::utils::base64")]
     pub nonce: Vec<u8>,
     #[serde(with = "crate::utils::base64")]
     pub filename_nonce: Vec<u8>,
     #[serde(with = "crate::utils::base64")]
     pub filename: Vec<u8>,
+    /// Size of the plaintext after padding. The true size is in [`FileInfo`] if padded.
     pub size: usize,
     pub block_size: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<PaddingScheme>,
+    /// File key wrapped to each recipient. Empty if the share is encrypted with a passphrase.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<WrappedKey>,
+    /// File key wrapped under each passphrase. Empty if the file key is derived from a single
+    /// passphrase directly.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_slots: Vec<KeySlot>,
+    /// Encrypted [`FileInfo`], set once every chunk is uploaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<EncryptedFileInfo>,
+    /// Unix timestamp of creation. `None` for shares created before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    /// Unix timestamp of when [`Self::info`] was set, which finishes the upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<i64>,
+    /// Unix timestamp after which the share cannot be downloaded. `None` if it never expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    /// Unix timestamp of when an administrator disabled downloads of the share. `None` if
+    /// enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_at: Option<i64>,
+}
+
+/// Number of chunks of a file of `size` bytes. Every chunk is full except the last, which is
+/// always uploaded and may be empty.
+fn chunk_count(size: usize, block_size: usize) -> usize {
+    size / block_size + 1
+}
+
+impl Metadata {
+    pub fn chunk_count(&self) -> usize {
+        chunk_count(self.size, self.block_size)
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn index_entry(&self) -> IndexEntry {
+        IndexEntry {
+            creator_email: self.creator_email.clone(),
+            size: self.size,
+            block_size: self.block_size,
+            padding: self.padding,
+            created_at: self.created_at,
+            completed_at: self.completed_at,
+            expires_at: self.expires_at,
+            completed: self.info.is_some(),
+        }
+    }
+
+    /// Public view of the share. The creator and names of key slots are included only for the
+    /// owner.
+    pub fn into_share_info(self, is_owner: bool) -> ShareInfo {
+        let Self {
+            creator_email,
+            salt,
+            key_check,
+            nonce,
+            filename_nonce,
+            filename,
+            size,
+            block_size,
+            padding,
+            recipients,
+            key_slots,
+            info,
+            created_at,
+            completed_at,
+            expires_at: _,
+            disabled_at: _,
+        } = self;
+        ShareInfo {
+            creator_email: is_owner.then_some(creator_email),
+            salt,
+            key_check,
+            nonce,
+            filename_nonce,
+            filename,
+            size,
+            block_size,
+            padding,
+            recipients,
+            key_slots: if is_owner {
+                key_slots
+            } else {
+                key_slots
+                    .into_iter()
+                    .map(|slot| KeySlot {
+                        name: String::new(),
+                        ..slot
+                    })
+                    .collect()
+            },
+            info,
+            created_at,
+            completed_at,
+        }
+    }
+}
+
+/// Everything needed to download and decrypt a share, without personal data of the creator.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ShareInfo {
+    /// Only present if the share is requested by its owner.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator_email: Option<String>,
+    #[serde(with = "crate::utils::base64")]
+    pub salt: Vec<u8>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::base64::option"
+    )]
+    pub key_check: Option<Vec<u8>>,
+    #[serde(with = "crate::utils::base64")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "crate::utils::base64")]
+    pub filename_nonce: Vec<u8>,
+    #[serde(with = "crate::utils::base64")]
+    pub filename: Vec<u8>,
+    /// Size of the plaintext after padding. The true size is in [`FileInfo`] if padded.
+    pub size: usize,
+    pub block_size: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<PaddingScheme>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<WrappedKey>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_slots: Vec<KeySlot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<EncryptedFileInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<i64>,
+}
+
+impl ShareInfo {
+    pub fn chunk_count(&self) -> usize {
+        chunk_count(self.size, self.block_size)
+    }
+}
+
+/// Index of every share, so that shares can be listed without fetching each metadata.
+///
+/// Kept in sync by the backend on a best-effort basis. `nanum-admin reindex` rebuilds it.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ShareIndex {
+    pub shares: BTreeMap<String, IndexEntry>,
+}
+
+/// Fields of [`Metadata`] needed to list shares.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexEntry {
+    pub creator_email: String,
+    /// Size of the plaintext after padding
+    pub size: usize,
+    pub block_size: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<PaddingScheme>,
+    pub created_at: Option<i64>,
+    #[serde(default)]
+    pub completed_at: Option<i64>,
+    pub expires_at: Option<i64>,
+    /// Whether the file info is uploaded, which is the last step of an upload
+    pub completed: bool,
+}
+
+impl IndexEntry {
+    pub fn summary(&self, id: String) -> ShareSummary {
+        ShareSummary {
+            id,
+            size: self.size,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            downloads: None,
+        }
+    }
+}
+
+/// Share as listed to its owner.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ShareSummary {
+    pub id: String,
+    /// Size of the plaintext after padding
+    pub size: usize,
+    pub created_at: Option<i64>,
+    pub expires_at: Option<i64>,
+    /// Number of downloads. Only counted if the server keeps share records in a database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downloads: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct MetadataCreationReq {
     #[serde(with = "crate::utils::base64")]
     pub salt: Vec<u8>,
+    /// See [`crate::key_check`]. Absent for shares uploaded before key checks.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::base64::option"
+    )]
+    pub key_check: Option<Vec<u8>>,
     #[serde(with = "crate::utils::base64")]
     pub nonce: Vec<u8>,
     #[serde(with = "crate::utils::base64")]
     pub filename_nonce: Vec<u8>,
     #[serde(with = "crate::utils::base64")]
     pub filename: Vec<u8>,
+    /// Size of the plaintext after padding. The true size is in [`FileInfo`] if padded.
     pub size: usize,
     pub block_size: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<PaddingScheme>,
+    /// File key wrapped to each recipient. Empty if the share is encrypted with a passphrase.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<WrappedKey>,
+    /// File key wrapped under each passphrase. Empty if the file key is derived from a single
+    /// passphrase directly.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_slots: Vec<KeySlot>,
 }
 
 impl MetadataCreationReq {
-    pub fn into_metadata(self, creator_email: String) -> Metadata {
+    pub fn into_metadata(self, creator_email: String, created_at: i64) -> Metadata {
         let Self {
             salt,
+            key_check,
             nonce,
             filename_nonce,
             filename,
             size,
             block_size,
+            padding,
+            recipients,
+            key_slots,
         } = self;
         Metadata {
             creator_email,
             salt,
+            key_check,
             nonce,
             filename_nonce,
             filename,
             size,
             block_size,
+            padding,
+            recipients,
+            key_slots,
+            info: None,
+            created_at: Some(created_at),
+            completed_at: None,
+            expires_at: None,
+            disabled_at: None,
         }
     }
 }
+
+/// Attributes of the uploaded file, encrypted with the same key as the file.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct FileInfo {
+    /// Size of the plaintext before padding. `None` if not padded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    pub mime_type: Option<String>,
+    /// Unix timestamp of the last modification of the original file
+    pub modified_at: Option<i64>,
+    /// SHA-256 digest of the plaintext
+    #[serde(with = "crate::utils::base64")]
+    pub sha256: Vec<u8>,
+    pub description: Option<String>,
+}
+
+/// JSON serialized [`FileInfo`], encrypted with XChaCha20Poly1305.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EncryptedFileInfo {
+    #[serde(with = "crate::utils::base64")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "crate::utils::base64")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// File key encrypted under a single passphrase. See [`crate::key_slot`].
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct KeySlot {
+    /// Label for the owner to tell slots apart, like the name of the recipient. Empty in the
+    /// [`ShareInfo`] of anyone else.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(with = "crate::utils::base64")]
+    pub salt: Vec<u8>,
+    #[serde(with = "crate::utils::base64")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "crate::utils::base64")]
+    pub wrapped_key: Vec<u8>,
+}
+
+/// File key encrypted to a single X25519 recipient. See [`crate::recipient`].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WrappedKey {
+    #[serde(with = "crate::utils::base64")]
+    pub ephemeral_public: Vec<u8>,
+    #[serde(with = "crate::utils::base64")]
+    pub wrapped_key: Vec<u8>,
+}
+
+/// Server settings which clients have to follow.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ClientConfig {
+    /// Minimum estimated entropy of passphrases in bits. See [`crate::passphrase`].
+    pub min_passphrase_entropy: f64,
+}
+
+/// Sessions revoked by administrators, keyed by email.
+///
+/// Any session authenticated at or before the recorded unix timestamp is rejected.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Revocations {
+    #[serde(default)]
+    pub revoked_before: HashMap<String, i64>,
+}
+
+impl Revocations {
+    pub fn is_revoked(&self, emails: &[String], auth_time: i64) -> bool {
+        emails.iter().any(|email| {
+            self.revoked_before
+                .get(email)
+                .is_some_and(|revoked_before| auth_time <= *revoked_before)
+        })
+    }
+
+    pub fn revoke(&mut self, email: String, now: i64) {
+        self.revoked_before.insert(email, now);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Create shares and upload files
+    Upload,
+}
+
+/// Personal API token as stored. The token itself is never stored, only its hash.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub creator_email: String,
+    #[serde(with = "crate::utils::base64")]
+    pub secret_hash: Vec<u8>,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    #[serde(default)]
+    pub last_used_at: Option<i64>,
+}
+
+impl ApiToken {
+    pub fn info(&self) -> ApiTokenInfo {
+        ApiTokenInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            scopes: self.scopes.clone(),
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            last_used_at: self.last_used_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ApiTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiTokenCreationReq {
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    pub expires_in_days: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiTokenCreationResp {
+    pub token: String,
+    pub info: ApiTokenInfo,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata_with_slot() -> Metadata {
+        MetadataCreationReq {
+            salt: vec![0; 32],
+            key_check: None,
+            nonce: vec![0; 19],
+            filename_nonce: vec![0; 24],
+            filename: vec![0; 16],
+            size: 0,
+            block_size: 1024,
+            padding: None,
+            recipients: Vec::new(),
+            key_slots: vec![KeySlot {
+                name: "alice".to_string(),
+                salt: vec![0; 32],
+                nonce: vec![0; 24],
+                wrapped_key: vec![0; 48],
+            }],
+        }
+        .into_metadata("owner@example.com".to_string(), 0)
+    }
+
+    #[test]
+    fn share_info_hides_owner_details_from_others() {
+        let info = metadata_with_slot().into_share_info(false);
+        assert_eq!(info.creator_email, None);
+        assert_eq!(info.key_slots.len(), 1);
+        assert_eq!(info.key_slots[0].name, "");
+    }
+
+    #[test]
+    fn share_info_shows_owner_details_to_owner() {
+        let info = metadata_with_slot().into_share_info(true);
+        assert_eq!(info.creator_email.as_deref(), Some("owner@example.com"));
+        assert_eq!(info.key_slots[0].name, "alice");
+    }
+}
@@ -12,3 +12,22 @@ pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error>
         .decode(base64.as_bytes())
         .map_err(serde::de::Error::custom)
 }
+
+/// Same as the parent module, but for optional values.
+pub mod option {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(v) => super::serialize(v, s),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super")] Vec<u8>);
+
+        Ok(Option::<Wrapper>::deserialize(d)?.map(|Wrapper(v)| v))
+    }
+}
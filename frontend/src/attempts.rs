@@ -0,0 +1,67 @@
+//! Throttling of wrong passphrase attempts.
+//!
+//! Anyone with the metadata can guess offline, so this only slows down guessing by hand in the
+//! browser. Attempts are kept in the session storage, per share.
+
+use serde::{Deserialize, Serialize};
+use web_sys::Storage;
+
+/// Wrong attempts allowed before each attempt has to wait.
+const FREE_ATTEMPTS: u32 = 3;
+const MAX_DELAY_MS: f64 = 60. * 1000.;
+
+#[derive(Serialize, Deserialize, Default)]
+struct Attempts {
+    count: u32,
+    /// Milliseconds since the epoch of the last wrong attempt
+    last_failure: f64,
+}
+
+fn storage() -> Option<Storage> {
+    web_sys::window()?.session_storage().ok()?
+}
+
+fn storage_key(id: &str) -> String {
+    format!("nanum:attempts:{id}")
+}
+
+fn load(id: &str) -> Attempts {
+    storage()
+        .and_then(|storage| storage.get_item(&storage_key(id)).ok()?)
+        .and_then(|attempts| serde_json::from_str(&attempts).ok())
+        .unwrap_or_default()
+}
+
+/// Whether another attempt has to wait because of previous wrong attempts.
+///
+/// The delay doubles with each wrong attempt after the free ones, up to a minute.
+pub fn is_throttled(id: &str) -> bool {
+    let attempts = load(id);
+    if attempts.count < FREE_ATTEMPTS {
+        return false;
+    }
+    let delay = (1000. * 2f64.powi((attempts.count - FREE_ATTEMPTS) as i32)).min(MAX_DELAY_MS);
+    js_sys::Date::now() < attempts.last_failure + delay
+}
+
+pub fn record_failure(id: &str) {
+    let storage = match storage() {
+        Some(storage) => storage,
+        None => return,
+    };
+    let attempts = Attempts {
+        count: load(id).count.saturating_add(1),
+        last_failure: js_sys::Date::now(),
+    };
+    if let Ok(attempts) = serde_json::to_string(&attempts) {
+        if let Err(error) = storage.set_item(&storage_key(id), &attempts) {
+            log::error!("failed to save wrong attempts: {:?}", error);
+        }
+    }
+}
+
+pub fn clear(id: &str) {
+    if let Some(storage) = storage() {
+        let _ = storage.remove_item(&storage_key(id));
+    }
+}
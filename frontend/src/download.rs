@@ -1,18 +1,73 @@
 use aead::{generic_array::GenericArray, stream::DecryptorBE32, Aead, KeyInit};
 use chacha20poly1305::{Key, XChaCha20Poly1305};
-use gloo_net::http::Request;
+use gloo_net::http::{Request, Response};
 use hkdf::Hkdf;
 use js_sys::{Array, Uint8Array};
-use nanum_core::types::Metadata;
-use sha2::Sha256;
-use wasm_bindgen_futures::spawn_local;
+use nanum_core::{
+    key_check, key_slot,
+    recipient::{parse_identity, KeyError},
+    types::{FileInfo, ShareInfo},
+};
+use sha2::{Digest, Sha256};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
 use web_sys::{Event, HtmlInputElement, HtmlLinkElement, SubmitEvent, Url};
 use yew::{
-    function_component, html, use_callback, use_effect_with_deps, use_node_ref, use_state, Html,
-    NodeRef, Properties, TargetCast, UseStateHandle,
+    function_component, html, use_callback, use_effect_with_deps, use_memo, use_node_ref,
+    use_state, Html, NodeRef, Properties, TargetCast, UseStateHandle,
+};
+
+use crate::{
+    attempts, error::error_message, hooks::use_user, key_slots::KeySlots, navbar::NavBar,
+    tokens::format_timestamp,
 };
 
-use crate::navbar::NavBar;
+/// Times a chunk is fetched again after the server rate limited it.
+const MAX_RATE_LIMIT_RETRIES: usize = 10;
+/// Seconds spent waiting out the rate limit for a single chunk before giving up.
+const MAX_RATE_LIMIT_WAIT_SECS: u32 = 300;
+
+/// Fetches a chunk, waiting out the download rate limit of the server for a while. Once out of
+/// retries, the 429 response is returned for the caller to report.
+async fn fetch_chunk(id: &str, seq: usize) -> Result<Response, gloo_net::Error> {
+    let mut waited = 0;
+    for _ in 0..MAX_RATE_LIMIT_RETRIES {
+        let resp = Request::get(&format!("/api/file/{id}/{seq}"))
+            .send()
+            .await?;
+        if resp.status() != 429 {
+            return Ok(resp);
+        }
+        let retry_after = resp
+            .headers()
+            .get("retry-after")
+            .and_then(|secs| secs.parse::<u32>().ok())
+            .unwrap_or(1);
+        if retry_after.saturating_add(waited) > MAX_RATE_LIMIT_WAIT_SECS {
+            return Ok(resp);
+        }
+        log::info!("rate limited, retrying chunk {seq} after {retry_after}s");
+        sleep(retry_after * 1000).await;
+        waited += retry_after;
+    }
+    Request::get(&format!("/api/file/{id}/{seq}")).send().await
+}
+
+async fn sleep(millis: u32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let scheduled = web_sys::window().and_then(|window| {
+            window
+                .set_timeout_with_callback_and_timeout_and_arguments_0(
+                    &resolve,
+                    millis.min(i32::MAX as u32) as i32,
+                )
+                .ok()
+        });
+        if scheduled.is_none() {
+            let _ = resolve.call0(&wasm_bindgen::JsValue::NULL);
+        }
+    });
+    let _ = JsFuture::from(promise).await;
+}
 
 #[derive(Properties, PartialEq)]
 pub struct DownloadProps {
@@ -20,13 +75,351 @@ pub struct DownloadProps {
 }
 
 enum MetadataStatus {
-    Loaded(Metadata),
+    Loaded(Box<ShareInfo>),
     Loading,
     NotFound,
 }
 
+#[derive(Clone, PartialEq)]
+struct DownloadHandles {
+    error_state: UseStateHandle<&'static str>,
+    download_started: UseStateHandle<bool>,
+    decrypted_filename: UseStateHandle<Option<String>>,
+    file_info: UseStateHandle<Option<FileInfo>>,
+    verified: UseStateHandle<bool>,
+    progress: UseStateHandle<usize>,
+    a_ref: NodeRef,
+}
+
+/// Reads the key from the URL fragment of a link like `/{id}#key=...`.
+///
+/// The fragment is never sent to the server.
+fn fragment_key() -> Option<String> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    let key = hash
+        .strip_prefix('#')?
+        .split('&')
+        .find_map(|param| param.strip_prefix("key="))?;
+    if key.is_empty() {
+        None
+    } else {
+        Some(key.to_string())
+    }
+}
+
+enum RestoreError {
+    /// The input is not even a key
+    Invalid(&'static str),
+    /// The key is not the one of the share
+    Wrong(&'static str),
+}
+
+impl RestoreError {
+    fn message(&self) -> &'static str {
+        match self {
+            Self::Invalid(message) | Self::Wrong(message) => message,
+        }
+    }
+}
+
+fn derive_key(salt: &[u8], secret: &[u8]) -> Result<Key, hkdf::InvalidLength> {
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(salt), secret).expand(&[], &mut key)?;
+    Ok(key)
+}
+
+/// Recovers the secret the encryption key is derived from, which is the file key wrapped to the
+/// given private key or under the given passphrase, and checks it before anything is downloaded.
+///
+/// Shares uploaded before key slots derive the key from the passphrase itself.
+fn restore_secret(metadata: &ShareInfo, input: &str) -> Result<Vec<u8>, RestoreError> {
+    let (secret, wrong) = if !metadata.recipients.is_empty() {
+        let identity = parse_identity(input)
+            .map_err(KeyError::message)
+            .map_err(RestoreError::Invalid)?;
+        let wrong = "private key does not match any recipient";
+        let secret = identity
+            .unwrap_any(&metadata.recipients)
+            .ok_or(RestoreError::Wrong(wrong))?;
+        (secret, wrong)
+    } else if !metadata.key_slots.is_empty() {
+        let wrong = "wrong passphrase";
+        let secret =
+            key_slot::unwrap_any(&metadata.key_slots, input).ok_or(RestoreError::Wrong(wrong))?;
+        (secret, wrong)
+    } else {
+        (input.as_bytes().to_vec(), "wrong passphrase")
+    };
+
+    let matches = if let Some(check) = &metadata.key_check {
+        key_check::verify(&metadata.salt, &secret, check)
+    } else {
+        // shares uploaded before key checks can only be checked by decrypting the filename
+        derive_key(&metadata.salt, &secret).is_ok_and(|key| {
+            XChaCha20Poly1305::new(&key)
+                .decrypt(
+                    GenericArray::from_slice(metadata.filename_nonce.as_ref()),
+                    metadata.filename.as_ref(),
+                )
+                .is_ok()
+        })
+    };
+    if matches {
+        Ok(secret)
+    } else {
+        Err(RestoreError::Wrong(wrong))
+    }
+}
+
+/// Restores the key, decrypts the filename, then fetches and decrypts every chunk and saves the
+/// file.
+fn start_download(id: String, metadata: &ShareInfo, secret: &[u8], handles: &DownloadHandles) {
+    let DownloadHandles {
+        error_state,
+        download_started,
+        decrypted_filename: decrypted_filename_state,
+        file_info: file_info_state,
+        verified,
+        progress,
+        a_ref,
+    } = handles;
+
+    error_state.set("");
+
+    // Reference: https://github.com/skystar-p/hako/blob/main/webapp/src/download.rs
+
+    // decrypt filename first
+    // restore key from passphrase
+    let key = match derive_key(&metadata.salt, secret) {
+        Ok(key) => key,
+        Err(err) => {
+            log::error!("cannot expand passphrase by hkdf: {:?}", err);
+            error_state.set("cannot expand passphrase by hkdf");
+            return;
+        }
+    };
+    let cipher = XChaCha20Poly1305::new(&key);
+    let filename_nonce = GenericArray::from_slice(metadata.filename_nonce.as_ref());
+    let decrypted_filename = {
+        match cipher.decrypt(filename_nonce, metadata.filename.as_ref()) {
+            Ok(decrypted) => decrypted,
+            Err(err) => {
+                log::error!("failed to decrypt filename: {:?}", err);
+                error_state.set("failed to decrypt filename");
+                return;
+            }
+        }
+    };
+
+    decrypted_filename_state.set(Some(
+        String::from_utf8_lossy(&decrypted_filename).to_string(),
+    ));
+
+    // shares uploaded before file info, or whose upload was interrupted, have none
+    let file_info = if let Some(info) = &metadata.info {
+        let info_nonce = GenericArray::from_slice(info.nonce.as_ref());
+        let decrypted_info = match cipher.decrypt(info_nonce, info.ciphertext.as_ref()) {
+            Ok(decrypted) => decrypted,
+            Err(err) => {
+                log::error!("failed to decrypt file info: {:?}", err);
+                error_state.set("failed to decrypt file info");
+                return;
+            }
+        };
+        match serde_json::from_slice::<FileInfo>(&decrypted_info) {
+            Ok(info) => Some(info),
+            Err(err) => {
+                log::error!("failed to read file info: {:?}", err);
+                error_state.set("failed to read file info");
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    // the true size of a padded file is only known from the file info
+    if metadata.padding.is_some() && file_info.is_none() {
+        error_state.set("upload of this file was not completed");
+        return;
+    }
+    file_info_state.set(file_info.clone());
+    download_started.set(true);
+
+    let seq_count = metadata.chunk_count();
+
+    let error_state = error_state.clone();
+    let metadata = metadata.clone();
+    let verified = verified.clone();
+    let progress = progress.clone();
+    let a_ref = a_ref.clone();
+    spawn_local(async move {
+        // make cipher
+        let cipher = XChaCha20Poly1305::new(&key);
+        let stream_nonce = GenericArray::from_slice(metadata.nonce.as_ref());
+        let mut decryptor = DecryptorBE32::from_aead(cipher, stream_nonce);
+
+        // preallocate buffers
+        let mut body = Vec::<u8>::with_capacity(metadata.size);
+
+        for seq in 1..=(seq_count - 1) {
+            let resp = match fetch_chunk(&id, seq).await {
+                Ok(resp) => resp,
+                Err(error) => {
+                    log::error!("failed to fetch chunk: {:?}", error);
+                    error_state.set("failed to fetch chunk");
+                    return;
+                }
+            };
+            if resp.status() != 200 {
+                log::error!("failed to fetch chunk. status code: {}", resp.status());
+                error_state.set(error_message(resp, "failed to fetch chunk").await);
+                return;
+            }
+            let chunk = match resp.binary().await {
+                Ok(resp) => resp,
+                Err(error) => {
+                    log::error!("failed to read chunk response: {:?}", error);
+                    error_state.set("failed to read chunk response");
+                    return;
+                }
+            };
+
+            let mut res = match decryptor.decrypt_next(chunk.as_slice()) {
+                Ok(res) => res,
+                Err(error) => {
+                    log::error!("failed to decrypt chunk: {:?}", error);
+                    error_state.set("failed to decrypt chunk");
+                    return;
+                }
+            };
+
+            body.append(&mut res);
+            progress.set(body.len());
+        }
+
+        let resp = match fetch_chunk(&id, seq_count).await {
+            Ok(resp) => resp,
+            Err(error) => {
+                log::error!("failed to fetch chunk: {:?}", error);
+                error_state.set("failed to fetch chunk");
+                return;
+            }
+        };
+        if resp.status() != 200 {
+            log::error!("failed to fetch chunk. status code: {}", resp.status());
+            error_state.set(error_message(resp, "failed to fetch chunk").await);
+            return;
+        }
+        let chunk = match resp.binary().await {
+            Ok(resp) => resp,
+            Err(error) => {
+                log::error!("failed to read chunk response: {:?}", error);
+                error_state.set("failed to read chunk response");
+                return;
+            }
+        };
+
+        let mut res = match decryptor.decrypt_last(chunk.as_slice()) {
+            Ok(res) => res,
+            Err(error) => {
+                log::error!("failed to decrypt chunk: {:?}", error);
+                error_state.set("failed to decrypt chunk");
+                return;
+            }
+        };
+
+        body.append(&mut res);
+        progress.set(body.len());
+
+        if body.len() != metadata.size {
+            log::error!(
+                "received bytes does not match expected size. expected: {}, actual: {}",
+                metadata.size,
+                body.len()
+            );
+            error_state.set("received bytes does not match expected size");
+            return;
+        }
+
+        if let Some(file_info) = &file_info {
+            // strip the padding after the file
+            if let Some(size) = file_info.size {
+                body.truncate(size);
+            }
+            if Sha256::digest(&body).as_slice() != file_info.sha256.as_slice() {
+                log::error!("checksum of the received file does not match");
+                error_state.set("checksum mismatch. The file may be corrupted");
+                return;
+            }
+            verified.set(true);
+        }
+
+        let a = match a_ref.cast::<HtmlLinkElement>() {
+            Some(a) => a,
+            None => {
+                log::error!("failed to get a ref");
+                error_state.set("failed to get a ref");
+                return;
+            }
+        };
+
+        // Touching filesystem in browser is strictly prohibited because of security
+        // context, so we cannot pipe Vec<u8> into file directly. In order to invoke file
+        // download for user, we have to convert it into `Blob` object and retrieve its
+        // object url(which will resides in memory).
+        // But we cannot use Vec<u8>'s reference directly because `Blob` is immutable
+        // itself, so we have to full-copy the whole buffer. Not efficient of course...
+        // In addition, moving WASM's linear memory into JS's `Uint8Array` also cause full
+        // copy of buffer, which is worse... (consumes `file_size` * 3 amount of memory)
+        // So in here, we use unsafe method `Uint8Array::view()` which just unsafely map
+        // WASM's memory into linear `Uint8Array`'s memory representation, which will not
+        // cause copy of memory. `mem_view` and decrypted content should have same
+        // lifetime, and those should not be reallocated.
+        unsafe {
+            let blob_parts = Array::new();
+            let mem_view = Uint8Array::view(&body);
+            blob_parts.push(&mem_view);
+            let decrypted_blob = {
+                // causes full copy of buffer. this will consumes lots of memory, but there
+                // are no workaround currently.
+                match web_sys::Blob::new_with_u8_array_sequence(&blob_parts) {
+                    Ok(blob) => blob,
+                    Err(err) => {
+                        log::error!("failed to make data into blob: {:?}", err);
+                        error_state.set("failed to make data into blob");
+                        return;
+                    }
+                }
+            };
+            let obj_url = {
+                match Url::create_object_url_with_blob(&decrypted_blob) {
+                    Ok(u) => u,
+                    Err(err) => {
+                        log::error!("failed to make blob into object url: {:?}", err);
+                        error_state.set("failed to make blob into object url");
+                        return;
+                    }
+                }
+            };
+
+            a.set_href(&obj_url);
+            // invoke download action
+            a.click();
+
+            // immediately revoke object url so that memory consumed by `Blob` object will
+            // soon released by GC.
+            if let Err(e) = Url::revoke_object_url(&obj_url) {
+                log::error!("failed to revoke object url: {:?}", e);
+                error_state.set("failed to revoke object url");
+            }
+        }
+    });
+}
+
 #[function_component(Download)]
 pub fn download(props: &DownloadProps) -> Html {
+    let user = use_user();
+
     let error = use_state::<&'static str, _>(|| "");
 
     let metadata = use_state(|| MetadataStatus::Loading);
@@ -53,11 +446,11 @@ pub fn download(props: &DownloadProps) -> Html {
                             return;
                         } else {
                             log::error!("failed to fetch metadata. status code: {}", status);
-                            error_state.set("failed to fetch metadata");
+                            error_state.set(error_message(resp, "failed to fetch metadata").await);
                             return;
                         }
                     }
-                    let fetched_metadata: Metadata = match resp.json().await {
+                    let fetched_metadata: ShareInfo = match resp.json().await {
                         Ok(resp) => resp,
                         Err(error) => {
                             log::error!("failed to read metadata response: {:?}", error);
@@ -65,7 +458,7 @@ pub fn download(props: &DownloadProps) -> Html {
                             return;
                         }
                     };
-                    metadata.set(MetadataStatus::Loaded(fetched_metadata));
+                    metadata.set(MetadataStatus::Loaded(Box::new(fetched_metadata)));
                 });
                 || ()
             }
@@ -79,6 +472,8 @@ pub fn download(props: &DownloadProps) -> Html {
 
     let download_started = use_state(|| false);
     let decrypted_filename = use_state::<Option<String>, _>(|| None);
+    let file_info = use_state::<Option<FileInfo>, _>(|| None);
+    let verified = use_state(|| false);
     let progress = use_state(|| 0usize);
 
     let on_passphrase_change = use_callback(
@@ -89,245 +484,75 @@ pub fn download(props: &DownloadProps) -> Html {
         passphrase.clone(),
     );
 
-    let onsubmit = use_callback::<
-        _,
-        _,
-        _,
-        (
-            UseStateHandle<&'static str>,
-            UseStateHandle<String>,
-            UseStateHandle<bool>,
-            UseStateHandle<Option<String>>,
-            UseStateHandle<usize>,
-            NodeRef,
-        ),
-    >(
+    let handles = DownloadHandles {
+        error_state: error.clone(),
+        download_started: download_started.clone(),
+        decrypted_filename: decrypted_filename.clone(),
+        file_info: file_info.clone(),
+        verified: verified.clone(),
+        progress: progress.clone(),
+        a_ref: a_ref.clone(),
+    };
+
+    let onsubmit = use_callback(
         {
             let id = props.id.clone();
             let metadata = metadata.clone();
             move |e: SubmitEvent,
-                  (
-                error_state,
-                passphrase,
-                download_started,
-                decrypted_filename_state,
-                progress,
-                a_ref,
-            )| {
+                  (handles, passphrase): &(DownloadHandles, UseStateHandle<String>)| {
                 e.prevent_default();
 
-                if **download_started || passphrase.is_empty() {
-                    return;
-                }
-
-                let metadata = if let MetadataStatus::Loaded(metadata) = &*metadata {
-                    metadata
-                } else {
-                    return;
-                };
-
-                error_state.set("");
-                download_started.set(true);
-
-                // Reference: https://github.com/skystar-p/hako/blob/main/webapp/src/download.rs
-
-                // decrypt filename first
-                // restore key from passphrase
-                let h = Hkdf::<Sha256>::new(Some(metadata.salt.as_ref()), passphrase.as_bytes());
-                let mut key_slice = [0u8; 32];
-                if let Err(err) = h.expand(&[], &mut key_slice[..]) {
-                    log::error!("cannot expand passphrase by hkdf: {:?}", err);
-                    error_state.set("cannot expand passphrase by hkdf");
+                if *handles.download_started || passphrase.is_empty() {
                     return;
                 }
-                let key = Key::clone_from_slice(&key_slice);
-                let cipher = XChaCha20Poly1305::new(&key);
-                let filename_nonce = GenericArray::from_slice(metadata.filename_nonce.as_ref());
-                let decrypted_filename = {
-                    match cipher.decrypt(filename_nonce, metadata.filename.as_ref()) {
-                        Ok(decrypted) => decrypted,
-                        Err(err) => {
-                            log::error!("failed to decrypt filename: {:?}", err);
-                            error_state.set("failed to decrypt filename");
-                            return;
-                        }
-                    }
-                };
 
-                decrypted_filename_state.set(Some(
-                    String::from_utf8_lossy(&decrypted_filename).to_string(),
-                ));
-
-                let seq_count = (metadata.size as f64 / metadata.block_size as f64).ceil() as usize;
-
-                let error_state = error_state.clone();
-                let id = id.clone();
-                let metadata = metadata.clone();
-                let progress = progress.clone();
-                let a_ref = a_ref.clone();
-                spawn_local(async move {
-                    // make cipher
-                    let cipher = XChaCha20Poly1305::new(&key);
-                    let stream_nonce = GenericArray::from_slice(metadata.nonce.as_ref());
-                    let mut decryptor = DecryptorBE32::from_aead(cipher, stream_nonce);
-
-                    // preallocate buffers
-                    let mut body = Vec::<u8>::with_capacity(metadata.size);
-
-                    for seq in 1..=(seq_count - 1) {
-                        let resp = match Request::get(&format!("/api/file/{id}/{seq}")).send().await
-                        {
-                            Ok(resp) => resp,
-                            Err(error) => {
-                                log::error!("failed to fetch chunk: {:?}", error);
-                                error_state.set("failed to fetch chunk");
-                                return;
-                            }
-                        };
-                        if resp.status() != 200 {
-                            log::error!("failed to fetch chunk. status code: {}", resp.status());
-                            error_state.set("failed to fetch chunk");
-                            return;
-                        }
-                        let chunk = match resp.binary().await {
-                            Ok(resp) => resp,
-                            Err(error) => {
-                                log::error!("failed to read chunk response: {:?}", error);
-                                error_state.set("failed to read chunk response");
-                                return;
-                            }
-                        };
-
-                        let mut res = match decryptor.decrypt_next(chunk.as_slice()) {
-                            Ok(res) => res,
-                            Err(error) => {
-                                log::error!("failed to decrypt chunk: {:?}", error);
-                                error_state.set("failed to decrypt chunk");
-                                return;
-                            }
-                        };
-
-                        body.append(&mut res);
-                        progress.set(body.len());
-                    }
-
-                    let resp = match Request::get(&format!("/api/file/{id}/{seq_count}"))
-                        .send()
-                        .await
-                    {
-                        Ok(resp) => resp,
-                        Err(error) => {
-                            log::error!("failed to fetch chunk: {:?}", error);
-                            error_state.set("failed to fetch chunk");
-                            return;
-                        }
-                    };
-                    if resp.status() != 200 {
-                        log::error!("failed to fetch chunk. status code: {}", resp.status());
-                        error_state.set("failed to fetch chunk");
+                if let MetadataStatus::Loaded(metadata) = &*metadata {
+                    if attempts::is_throttled(&id) {
+                        handles
+                            .error_state
+                            .set("too many wrong attempts. Wait a moment and try again");
                         return;
                     }
-                    let chunk = match resp.binary().await {
-                        Ok(resp) => resp,
-                        Err(error) => {
-                            log::error!("failed to read chunk response: {:?}", error);
-                            error_state.set("failed to read chunk response");
-                            return;
+                    match restore_secret(metadata, passphrase) {
+                        Ok(secret) => {
+                            attempts::clear(&id);
+                            start_download(id.clone(), metadata, &secret, handles);
                         }
-                    };
-
-                    let mut res = match decryptor.decrypt_last(chunk.as_slice()) {
-                        Ok(res) => res,
                         Err(error) => {
-                            log::error!("failed to decrypt chunk: {:?}", error);
-                            error_state.set("failed to decrypt chunk");
-                            return;
+                            if let RestoreError::Wrong(_) = error {
+                                attempts::record_failure(&id);
+                            }
+                            handles.error_state.set(error.message());
                         }
-                    };
-
-                    body.append(&mut res);
-                    progress.set(body.len());
-
-                    if body.len() != metadata.size {
-                        log::error!(
-                            "received bytes does not match expected size. expected: {}, actual: {}",
-                            metadata.size,
-                            body.len()
-                        );
-                        error_state.set("received bytes does not match expected size");
-                        return;
                     }
+                }
+            }
+        },
+        (handles.clone(), passphrase),
+    );
 
-                    let a = match a_ref.cast::<HtmlLinkElement>() {
-                        Some(a) => a,
-                        None => {
-                            log::error!("failed to get a ref");
-                            error_state.set("failed to get a ref");
-                            return;
-                        }
-                    };
-
-                    // Touching filesystem in browser is strictly prohibited because of security
-                    // context, so we cannot pipe Vec<u8> into file directly. In order to invoke file
-                    // download for user, we have to convert it into `Blob` object and retrieve its
-                    // object url(which will resides in memory).
-                    // But we cannot use Vec<u8>'s reference directly because `Blob` is immutable
-                    // itself, so we have to full-copy the whole buffer. Not efficient of course...
-                    // In addition, moving WASM's linear memory into JS's `Uint8Array` also cause full
-                    // copy of buffer, which is worse... (consumes `file_size` * 3 amount of memory)
-                    // So in here, we use unsafe method `Uint8Array::view()` which just unsafely map
-                    // WASM's memory into linear `Uint8Array`'s memory representation, which will not
-                    // cause copy of memory. `mem_view` and decrypted content should have same
-                    // lifetime, and those should not be reallocated.
-                    unsafe {
-                        let blob_parts = Array::new();
-                        let mem_view = Uint8Array::view(&body);
-                        blob_parts.push(&mem_view);
-                        let decrypted_blob = {
-                            // causes full copy of buffer. this will consumes lots of memory, but there
-                            // are no workaround currently.
-                            match web_sys::Blob::new_with_u8_array_sequence(&blob_parts) {
-                                Ok(blob) => blob,
-                                Err(err) => {
-                                    log::error!("failed to make data into blob: {:?}", err);
-                                    error_state.set("failed to make data into blob");
-                                    return;
-                                }
-                            }
-                        };
-                        let obj_url = {
-                            match Url::create_object_url_with_blob(&decrypted_blob) {
-                                Ok(u) => u,
-                                Err(err) => {
-                                    log::error!("failed to make blob into object url: {:?}", err);
-                                    error_state.set("failed to make blob into object url");
-                                    return;
-                                }
-                            }
-                        };
-
-                        a.set_href(&obj_url);
-                        // invoke download action
-                        a.click();
-
-                        // immediately revoke object url so that memory consumed by `Blob` object will
-                        // soon released by GC.
-                        if let Err(e) = Url::revoke_object_url(&obj_url) {
-                            log::error!("failed to revoke object url: {:?}", e);
-                            error_state.set("failed to revoke object url");
+    // Shares created with a generated key carry the key in the URL fragment, so decryption can
+    // start right after the metadata is loaded.
+    let fragment_key = use_memo(|_| fragment_key(), ());
+    use_effect_with_deps(
+        {
+            let id = props.id.clone();
+            let metadata = metadata.clone();
+            move |loaded: &bool| {
+                if *loaded && !*handles.download_started {
+                    if let (Some(key), MetadataStatus::Loaded(metadata)) =
+                        (&*fragment_key, &*metadata)
+                    {
+                        match restore_secret(metadata, key) {
+                            Ok(secret) => start_download(id, metadata, &secret, &handles),
+                            Err(error) => handles.error_state.set(error.message()),
                         }
                     }
-                });
+                }
+                || ()
             }
         },
-        (
-            error.clone(),
-            passphrase,
-            download_started.clone(),
-            decrypted_filename.clone(),
-            progress.clone(),
-            a_ref.clone(),
-        ),
+        matches!(*metadata, MetadataStatus::Loaded(_)),
     );
 
     let inner = match &*metadata {
@@ -344,27 +569,72 @@ pub fn download(props: &DownloadProps) -> Html {
             };
             let decrypted_filename_show = if let Some(filename) = &*decrypted_filename {
                 html! {
-                    <div class="w-full mt-4">{filename}</div>
+                    <div class="w-full mt-4 break-all">{filename}</div>
+                }
+            } else {
+                html! { <></> }
+            };
+            let file_info_show = if let Some(info) = &*file_info {
+                html! {
+                    <div class="w-full mt-2 text-sm">
+                        if let Some(mime_type) = &info.mime_type {
+                            <div>{"Type: "}{mime_type}</div>
+                        }
+                        if let Some(modified_at) = info.modified_at {
+                            <div>{"Modified: "}{format_timestamp(modified_at)}</div>
+                        }
+                        if let Some(description) = &info.description {
+                            <div class="mt-2 whitespace-pre-wrap break-words">{description}</div>
+                        }
+                        if *verified {
+                            <div class="mt-2 text-success">{"Checksum verified"}</div>
+                        }
+                    </div>
                 }
             } else {
                 html! { <></> }
             };
 
+            let uploaded_show = match (metadata.completed_at, metadata.created_at) {
+                (Some(completed_at), _) => html! {
+                    <div class="w-full mb-2 text-sm">{"Uploaded: "}{format_timestamp(completed_at)}</div>
+                },
+                (None, Some(created_at)) => html! {
+                    <div class="w-full mb-2 text-sm">{"Upload started: "}{format_timestamp(created_at)}</div>
+                },
+                (None, None) => html! { <></> },
+            };
+
             html! {
                 <>
+                    {uploaded_show}
                     <form class="form-control w-full" {onsubmit}>
-                        <label class="label label-text">{"Passphrase"}</label>
-                        <input
-                            type="password"
-                            class="input input-bordered w-full"
-                            onchange={on_passphrase_change}
-                        />
+                        if metadata.recipients.is_empty() {
+                            <label class="label label-text">{"Passphrase"}</label>
+                            <input
+                                type="password"
+                                class="input input-bordered w-full"
+                                onchange={on_passphrase_change}
+                            />
+                        } else {
+                            <label class="label label-text">{"Private key"}</label>
+                            <input
+                                type="password"
+                                class="input input-bordered w-full font-mono"
+                                placeholder="AGE-SECRET-KEY-1..."
+                                onchange={on_passphrase_change}
+                            />
+                        }
                         if !*download_started {
                             <input type="submit" class="btn mt-4" value="Download" />
                         }
                     </form>
                     {progress_show}
                     {decrypted_filename_show}
+                    {file_info_show}
+                    if !metadata.key_slots.is_empty() && metadata.creator_email.is_some() {
+                        <KeySlots id={props.id.clone()} slots={metadata.key_slots.clone()} />
+                    }
                 </>
             }
         }
@@ -373,7 +643,7 @@ pub fn download(props: &DownloadProps) -> Html {
     };
 
     html! {
-        <NavBar>
+        <NavBar user={user.email()}>
             <div class="max-w-xs">
                 <div class="w-full flex justify-center mb-4">
                     <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="w-6 h-6">
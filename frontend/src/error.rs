@@ -0,0 +1,43 @@
+use gloo_net::http::Response;
+use nanum_core::error::{ApiError, ApiErrorCode};
+
+/// Reads the `ApiError` of a failed response.
+pub async fn read_error(resp: Response) -> Option<ApiError> {
+    match resp.json::<ApiError>().await {
+        Ok(error) => Some(error),
+        Err(error) => {
+            log::error!("failed to read error response: {:?}", error);
+            None
+        }
+    }
+}
+
+/// Reads the `ApiError` of a failed response and picks a message to show to the user.
+pub async fn error_message(resp: Response, fallback: &'static str) -> &'static str {
+    describe_error(read_error(resp).await.as_ref(), fallback)
+}
+
+/// Picks a message to show to the user for an `ApiError` read by [`read_error`].
+pub fn describe_error(error: Option<&ApiError>, fallback: &'static str) -> &'static str {
+    let error = match error {
+        Some(error) => error,
+        None => return fallback,
+    };
+    log::error!("{}: {}", fallback, error.message);
+
+    match error.code {
+        ApiErrorCode::InvalidId => "Invalid ID. Use letters, digits, '-' and '_'.",
+        ApiErrorCode::IdTaken => "ID already taken. Choose another ID or leave it empty.",
+        ApiErrorCode::NotFound => "Not found. The share may have been deleted.",
+        ApiErrorCode::ChunkExists | ApiErrorCode::AlreadyCompleted => {
+            "File is already uploaded to this ID."
+        }
+        ApiErrorCode::PayloadTooLarge => "Request is too large for the server.",
+        ApiErrorCode::TooManyRequests => "Too many requests. Try again later.",
+        ApiErrorCode::Unauthorized => "Sign in required.",
+        ApiErrorCode::UserNotAllowed => "Your account is not allowed to use this server.",
+        ApiErrorCode::Forbidden => "You are not allowed to do this.",
+        ApiErrorCode::Disabled => "This share is disabled by the administrator.",
+        ApiErrorCode::BadRequest | ApiErrorCode::Internal | ApiErrorCode::Unknown => fallback,
+    }
+}
@@ -0,0 +1,116 @@
+use gloo_net::http::Request;
+use nanum_core::{
+    error::{ApiError, ApiErrorCode},
+    types::ClientConfig,
+};
+use wasm_bindgen_futures::spawn_local;
+use yew::{hook, use_effect_with_deps, use_state, UseStateHandle};
+
+use crate::{error::read_error, types::User};
+
+#[derive(Clone, PartialEq)]
+pub enum UserStatus {
+    Loading,
+    SignedIn(String),
+    SignedOut,
+    Forbidden,
+}
+
+impl UserStatus {
+    /// Maps the error of a failed API call to a user status, if it is an auth failure. Other
+    /// 403s, e.g. for a missing token scope, are not about the account.
+    pub fn from_error(error: Option<&ApiError>) -> Option<Self> {
+        match error?.code {
+            ApiErrorCode::Unauthorized => Some(Self::SignedOut),
+            ApiErrorCode::UserNotAllowed => Some(Self::Forbidden),
+            _ => None,
+        }
+    }
+
+    pub fn email(&self) -> Option<String> {
+        if let Self::SignedIn(email) = self {
+            Some(email.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Fetches the signed in user.
+#[hook]
+pub fn use_user() -> UseStateHandle<UserStatus> {
+    let user = use_state(|| UserStatus::Loading);
+
+    use_effect_with_deps(
+        {
+            let user = user.clone();
+            move |_| {
+                spawn_local(async move {
+                    let resp = match Request::get("/api/user").send().await {
+                        Ok(resp) => resp,
+                        Err(error) => {
+                            log::error!("failed to fetch user: {:?}", error);
+                            return;
+                        }
+                    };
+                    if resp.status() != 200 {
+                        log::error!("failed to fetch user. status code: {}", resp.status());
+                        if let Some(status) =
+                            UserStatus::from_error(read_error(resp).await.as_ref())
+                        {
+                            user.set(status);
+                        }
+                        return;
+                    }
+                    let fetched_user: User = match resp.json().await {
+                        Ok(resp) => resp,
+                        Err(error) => {
+                            log::error!("failed to read user response: {:?}", error);
+                            return;
+                        }
+                    };
+                    user.set(UserStatus::SignedIn(fetched_user.primary_email));
+                });
+                || ()
+            }
+        },
+        (),
+    );
+
+    user
+}
+
+/// Fetches settings of the server. `None` until loaded, or if loading failed.
+#[hook]
+pub fn use_client_config() -> UseStateHandle<Option<ClientConfig>> {
+    let config = use_state(|| None);
+
+    use_effect_with_deps(
+        {
+            let config = config.clone();
+            move |_| {
+                spawn_local(async move {
+                    let resp = match Request::get("/api/config").send().await {
+                        Ok(resp) => resp,
+                        Err(error) => {
+                            log::error!("failed to fetch config: {:?}", error);
+                            return;
+                        }
+                    };
+                    if resp.status() != 200 {
+                        log::error!("failed to fetch config. status code: {}", resp.status());
+                        return;
+                    }
+                    match resp.json().await {
+                        Ok(fetched_config) => config.set(Some(fetched_config)),
+                        Err(error) => log::error!("failed to read config response: {:?}", error),
+                    }
+                });
+                || ()
+            }
+        },
+        (),
+    );
+
+    config
+}
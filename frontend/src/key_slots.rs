@@ -0,0 +1,234 @@
+use gloo_net::http::Request;
+use nanum_core::{key_slot, types::KeySlot};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Event, HtmlInputElement, InputEvent, SubmitEvent};
+use yew::{function_component, html, use_callback, use_state, Html, Properties, TargetCast};
+
+use crate::{
+    error::error_message,
+    hooks::use_client_config,
+    passphrase::{is_strong_enough, PassphraseStrength},
+};
+
+#[derive(Properties, PartialEq)]
+pub struct KeySlotsProps {
+    pub id: String,
+    pub slots: Vec<KeySlot>,
+}
+
+/// Lets the owner of a share hand out another passphrase or revoke one.
+#[function_component(KeySlots)]
+pub fn key_slots(props: &KeySlotsProps) -> Html {
+    let config = use_client_config();
+    let min_entropy = config.as_ref().map(|config| config.min_passphrase_entropy);
+
+    let error = use_state::<&'static str, _>(|| "");
+
+    let slots = use_state(|| props.slots.clone());
+
+    let current_passphrase = use_state(String::new);
+    let name = use_state(String::new);
+    let new_passphrase = use_state(String::new);
+
+    let on_current_passphrase_change = use_callback(
+        move |e: Event, current_passphrase| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            current_passphrase.set(input.value());
+        },
+        current_passphrase.clone(),
+    );
+    let on_name_change = use_callback(
+        move |e: Event, name| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            name.set(input.value());
+        },
+        name.clone(),
+    );
+    let on_new_passphrase_change = use_callback(
+        move |e: InputEvent, new_passphrase| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            new_passphrase.set(input.value());
+        },
+        new_passphrase.clone(),
+    );
+
+    let onsubmit =
+        use_callback(
+            move |e: SubmitEvent,
+                  (
+                id,
+                error_state,
+                slots,
+                current_passphrase,
+                name,
+                new_passphrase,
+                min_entropy,
+            )| {
+                e.prevent_default();
+
+                let name = name.trim();
+                if name.is_empty() || new_passphrase.is_empty() {
+                    return;
+                }
+                if !is_strong_enough(new_passphrase, *min_entropy) {
+                    error_state.set("Passphrase is too weak. Make it longer.");
+                    return;
+                }
+                if slots.iter().any(|slot| slot.name == name) {
+                    error_state.set("key slot name already exists");
+                    return;
+                }
+
+                error_state.set("");
+
+                // the file key is only ever known to clients, so an existing passphrase is needed
+                // to wrap it under the new one
+                let file_key = match key_slot::unwrap_any(slots, current_passphrase) {
+                    Some(file_key) => file_key,
+                    None => {
+                        error_state.set("wrong current passphrase");
+                        return;
+                    }
+                };
+                let slot = match key_slot::wrap(name.to_string(), new_passphrase, &file_key) {
+                    Ok(slot) => slot,
+                    Err(error) => {
+                        log::error!("cannot wrap key under passphrase: {:?}", error);
+                        error_state.set("cannot wrap key under passphrase");
+                        return;
+                    }
+                };
+
+                let id = id.clone();
+                let error_state = error_state.clone();
+                let slots = slots.clone();
+                spawn_local(async move {
+                    let req = match Request::post(&format!("/api/metadata/{id}/slots")).json(&slot)
+                    {
+                        Ok(req) => req,
+                        Err(error) => {
+                            log::error!("failed to make request: {:?}", error);
+                            error_state.set("failed to make request");
+                            return;
+                        }
+                    };
+                    let resp = match req.send().await {
+                        Ok(resp) => resp,
+                        Err(error) => {
+                            log::error!("failed to add key slot: {:?}", error);
+                            error_state.set("failed to add key slot");
+                            return;
+                        }
+                    };
+                    if resp.status() != 200 {
+                        log::error!("failed to add key slot. status code: {}", resp.status());
+                        error_state.set(error_message(resp, "failed to add key slot").await);
+                        return;
+                    }
+                    let mut new_slots = (*slots).clone();
+                    new_slots.push(slot);
+                    slots.set(new_slots);
+                });
+            },
+            (
+                props.id.clone(),
+                error.clone(),
+                slots.clone(),
+                current_passphrase,
+                name,
+                new_passphrase.clone(),
+                min_entropy,
+            ),
+        );
+
+    let on_revoke = use_callback(
+        move |name: String, (id, error_state, slots)| {
+            error_state.set("");
+
+            let id = id.clone();
+            let error_state = error_state.clone();
+            let slots = slots.clone();
+            spawn_local(async move {
+                let encoded_name = String::from(js_sys::encode_uri_component(&name));
+                let resp =
+                    match Request::delete(&format!("/api/metadata/{id}/slots/{encoded_name}"))
+                        .send()
+                        .await
+                    {
+                        Ok(resp) => resp,
+                        Err(error) => {
+                            log::error!("failed to revoke key slot: {:?}", error);
+                            error_state.set("failed to revoke key slot");
+                            return;
+                        }
+                    };
+                if resp.status() != 200 {
+                    log::error!("failed to revoke key slot. status code: {}", resp.status());
+                    error_state.set(error_message(resp, "failed to revoke key slot").await);
+                    return;
+                }
+                let mut new_slots = (*slots).clone();
+                new_slots.retain(|slot| slot.name != name);
+                slots.set(new_slots);
+            });
+        },
+        (props.id.clone(), error.clone(), slots.clone()),
+    );
+
+    let slot_rows = slots
+        .iter()
+        .map(|slot| {
+            let onclick = {
+                let name = slot.name.clone();
+                on_revoke.reform(move |_| name.clone())
+            };
+            html! {
+                <tr key={slot.name.clone()}>
+                    <td class="break-all">{&slot.name}</td>
+                    <td class="text-right">
+                        <button class="btn btn-error btn-xs" disabled={slots.len() <= 1} {onclick}>
+                            {"Revoke"}
+                        </button>
+                    </td>
+                </tr>
+            }
+        })
+        .collect::<Html>();
+
+    html! {
+        <div class="w-full mt-8">
+            <div class="text-lg mb-2">{"Key slots"}</div>
+            <table class="table table-compact w-full">
+                <tbody>
+                    {slot_rows}
+                </tbody>
+            </table>
+            <form class="form-control w-full" {onsubmit}>
+                <label class="label label-text">{"Current passphrase"}</label>
+                <input
+                    type="password"
+                    class="input input-bordered w-full"
+                    onchange={on_current_passphrase_change}
+                />
+                <label class="label label-text">{"New slot name"}</label>
+                <input
+                    type="text"
+                    maxlength={key_slot::MAX_NAME_LENGTH.to_string()}
+                    class="input input-bordered w-full"
+                    onchange={on_name_change}
+                />
+                <label class="label label-text">{"New passphrase"}</label>
+                <input
+                    type="password"
+                    class="input input-bordered w-full"
+                    oninput={on_new_passphrase_change}
+                />
+                <PassphraseStrength passphrase={(*new_passphrase).clone()} {min_entropy} />
+                <input type="submit" class="btn mt-4" value="Add passphrase" />
+            </form>
+            if !error.is_empty() {
+                <div class="alert alert-error mt-4">{&*error}</div>
+            }
+        </div>
+    }
+}
@@ -1,7 +1,14 @@
 mod app;
+mod attempts;
 mod download;
+mod error;
+mod hooks;
+mod key_slots;
 mod navbar;
+mod passphrase;
 mod route;
+mod shares;
+mod tokens;
 mod types;
 mod upload;
 
@@ -1,5 +1,7 @@
 use yew::{function_component, html, Children, Html, Properties};
 
+use crate::hooks::UserStatus;
+
 #[derive(Properties, PartialEq)]
 pub struct NavBarProps {
     #[prop_or_default]
@@ -14,11 +16,16 @@ pub fn navbar(props: &NavBarProps) -> Html {
             <div>
                 <div class="container mx-auto navbar">
                     <div class="flex-1">
-                        <h1 class="font-bold normal-case text-xl">{ "nanum" }</h1>
+                        <h1 class="font-bold normal-case text-xl"><a href="/">{ "nanum" }</a></h1>
                     </div>
                     if let Some(user) = &props.user {
-                        <div class="flex-none">
+                        <div class="flex-none gap-2">
+                            <a href="/shares" class="btn btn-ghost btn-sm">{ "My shares" }</a>
+                            <a href="/tokens" class="btn btn-ghost btn-sm">{ "API tokens" }</a>
                             <span>{ user }</span>
+                            <form method="post" action="/auth/logout">
+                                <input type="submit" class="btn btn-ghost btn-sm" value="Log out" />
+                            </form>
                         </div>
                     } else {
                         <></>
@@ -31,3 +38,24 @@ pub fn navbar(props: &NavBarProps) -> Html {
         </>
     }
 }
+
+#[derive(Properties, PartialEq)]
+pub struct SignInPromptProps {
+    pub status: UserStatus,
+}
+
+#[function_component(SignInPrompt)]
+pub fn sign_in_prompt(props: &SignInPromptProps) -> Html {
+    match props.status {
+        UserStatus::SignedOut => html! {
+            <div class="flex flex-col items-center gap-4">
+                <div>{"You are signed out. Sign in to continue."}</div>
+                <a href="/auth/github" class="btn">{"Sign in with GitHub"}</a>
+            </div>
+        },
+        UserStatus::Forbidden => html! {
+            <div class="alert alert-error">{"Your account is not allowed to use nanum."}</div>
+        },
+        _ => html! { <></> },
+    }
+}
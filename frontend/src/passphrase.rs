@@ -0,0 +1,47 @@
+use nanum_core::passphrase::estimate_entropy;
+use yew::{classes, function_component, html, Html, Properties};
+
+/// Whether `passphrase` satisfies the policy of the server. Passes if the policy is not loaded.
+pub fn is_strong_enough(passphrase: &str, min_entropy: Option<f64>) -> bool {
+    min_entropy.is_none_or(|min_entropy| estimate_entropy(passphrase) >= min_entropy)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct PassphraseStrengthProps {
+    pub passphrase: String,
+    pub min_entropy: Option<f64>,
+}
+
+#[function_component(PassphraseStrength)]
+pub fn passphrase_strength(props: &PassphraseStrengthProps) -> Html {
+    if props.passphrase.is_empty() {
+        return html! { <></> };
+    }
+
+    let entropy = estimate_entropy(&props.passphrase);
+    let min_entropy = props.min_entropy.unwrap_or(0.);
+    let (label, color) = if entropy < min_entropy * 0.6 {
+        ("Very weak", "progress-error")
+    } else if entropy < min_entropy {
+        ("Weak", "progress-warning")
+    } else {
+        ("Strong", "progress-success")
+    };
+    // full bar at 1.5 times of the minimum, or at 128 bits without a policy
+    let max = if min_entropy > 0. {
+        min_entropy * 1.5
+    } else {
+        128.
+    };
+
+    html! {
+        <div class="w-full mt-2">
+            <progress
+                class={classes!("progress", "w-full", color)}
+                value={format!("{}", entropy.min(max))}
+                max={format!("{}", max)}
+            />
+            <div class="label-text-alt">{format!("{label} (about {entropy:.0} bits)")}</div>
+        </div>
+    }
+}
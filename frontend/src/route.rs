@@ -1,12 +1,16 @@
 use yew::{html, Html};
 use yew_router::Routable;
 
-use crate::{download::Download, upload::Upload};
+use crate::{download::Download, shares::Shares, tokens::Tokens, upload::Upload};
 
 #[derive(Routable, Clone, PartialEq)]
 pub enum Route {
     #[at("/")]
     Upload,
+    #[at("/tokens")]
+    Tokens,
+    #[at("/shares")]
+    Shares,
     #[at("/:id")]
     Download { id: String },
 }
@@ -14,6 +18,8 @@ pub enum Route {
 pub fn switch(route: Route) -> Html {
     match route {
         Route::Upload => html! { <Upload /> },
+        Route::Tokens => html! { <Tokens /> },
+        Route::Shares => html! { <Shares /> },
         Route::Download { id } => html! { <Download {id} /> },
     }
 }
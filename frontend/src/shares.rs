@@ -0,0 +1,217 @@
+use gloo_net::http::Request;
+use nanum_core::types::ShareSummary;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use yew::{function_component, html, use_callback, use_effect_with_deps, use_state, Html};
+
+use crate::{
+    error::{describe_error, error_message, read_error},
+    hooks::{use_user, UserStatus},
+    navbar::{NavBar, SignInPrompt},
+    tokens::format_timestamp,
+};
+
+#[wasm_bindgen]
+extern "C" {
+    // `web_sys::Clipboard` is behind `web_sys_unstable_apis`
+    #[wasm_bindgen(js_namespace = ["navigator", "clipboard"], js_name = writeText)]
+    fn write_clipboard_text(text: &str) -> js_sys::Promise;
+}
+
+fn format_size(size: usize) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn share_link(id: &str) -> String {
+    let origin = web_sys::window()
+        .and_then(|window| window.location().origin().ok())
+        .unwrap_or_default();
+    format!("{origin}/{id}")
+}
+
+/// Lists shares of the signed in user, who can copy their links or delete them.
+#[function_component(Shares)]
+pub fn shares() -> Html {
+    let user = use_user();
+
+    let error = use_state::<&'static str, _>(|| "");
+
+    let shares = use_state::<Vec<ShareSummary>, _>(Vec::new);
+    // Bumped to refetch the share list.
+    let reload = use_state(|| 0usize);
+    let copied = use_state::<Option<String>, _>(|| None);
+
+    use_effect_with_deps(
+        {
+            let user = user.clone();
+            let error_state = error.clone();
+            let shares = shares.clone();
+            move |_| {
+                spawn_local(async move {
+                    let resp = match Request::get("/api/shares").send().await {
+                        Ok(resp) => resp,
+                        Err(error) => {
+                            log::error!("failed to fetch shares: {:?}", error);
+                            error_state.set("failed to fetch shares");
+                            return;
+                        }
+                    };
+                    if resp.status() != 200 {
+                        log::error!("failed to fetch shares. status code: {}", resp.status());
+                        let error = read_error(resp).await;
+                        if let Some(status) = UserStatus::from_error(error.as_ref()) {
+                            user.set(status);
+                            return;
+                        }
+                        error_state.set(describe_error(error.as_ref(), "failed to fetch shares"));
+                        return;
+                    }
+                    let fetched_shares: Vec<ShareSummary> = match resp.json().await {
+                        Ok(resp) => resp,
+                        Err(error) => {
+                            log::error!("failed to read shares response: {:?}", error);
+                            error_state.set("failed to read shares response");
+                            return;
+                        }
+                    };
+                    shares.set(fetched_shares);
+                });
+                || ()
+            }
+        },
+        *reload,
+    );
+
+    let on_copy = use_callback(
+        move |id: String, (error_state, copied)| {
+            let error_state = error_state.clone();
+            let copied = copied.clone();
+            spawn_local(async move {
+                if let Err(error) = JsFuture::from(write_clipboard_text(&share_link(&id))).await {
+                    log::error!("failed to copy link: {:?}", error);
+                    error_state.set("failed to copy link");
+                    return;
+                }
+                copied.set(Some(id));
+            });
+        },
+        (error.clone(), copied.clone()),
+    );
+
+    let on_delete = use_callback(
+        move |id: String, (error_state, reload)| {
+            let confirmed = web_sys::window()
+                .and_then(|window| {
+                    window
+                        .confirm_with_message(&format!("Delete {id}? This cannot be undone."))
+                        .ok()
+                })
+                .unwrap_or(false);
+            if !confirmed {
+                return;
+            }
+
+            error_state.set("");
+
+            let error_state = error_state.clone();
+            let reload = reload.clone();
+            spawn_local(async move {
+                let resp = match Request::delete(&format!("/api/shares/{id}")).send().await {
+                    Ok(resp) => resp,
+                    Err(error) => {
+                        log::error!("failed to delete share: {:?}", error);
+                        error_state.set("failed to delete share");
+                        return;
+                    }
+                };
+                if resp.status() != 200 {
+                    log::error!("failed to delete share. status code: {}", resp.status());
+                    error_state.set(error_message(resp, "failed to delete share").await);
+                    return;
+                }
+                reload.set(*reload + 1);
+            });
+        },
+        (error.clone(), reload),
+    );
+
+    // downloads are only counted if the server has a database
+    let show_downloads = shares.iter().any(|share| share.downloads.is_some());
+
+    let share_rows = shares
+        .iter()
+        .map(|share| {
+            let on_copy = {
+                let id = share.id.clone();
+                on_copy.reform(move |_| id.clone())
+            };
+            let on_delete = {
+                let id = share.id.clone();
+                on_delete.reform(move |_| id.clone())
+            };
+            let is_copied = copied.as_deref() == Some(share.id.as_str());
+            html! {
+                <tr key={share.id.clone()}>
+                    <td class="break-all"><a href={format!("/{}", share.id)} class="link">{&share.id}</a></td>
+                    <td>{format_size(share.size)}</td>
+                    <td>{share.created_at.map(format_timestamp).unwrap_or_else(|| "Unknown".to_string())}</td>
+                    <td>{share.expires_at.map(format_timestamp).unwrap_or_else(|| "Never".to_string())}</td>
+                    if show_downloads {
+                        <td>{share.downloads.unwrap_or_default()}</td>
+                    }
+                    <td class="text-right">
+                        <button class="btn btn-xs mr-2" onclick={on_copy}>
+                            {if is_copied { "Copied" } else { "Copy link" }}
+                        </button>
+                        <button class="btn btn-error btn-xs" onclick={on_delete}>{"Delete"}</button>
+                    </td>
+                </tr>
+            }
+        })
+        .collect::<Html>();
+
+    html! {
+        <NavBar user={user.email()}>
+            if matches!(*user, UserStatus::SignedOut | UserStatus::Forbidden) {
+                <SignInPrompt status={(*user).clone()} />
+            } else {
+                <div class="w-full max-w-3xl">
+                    <table class="table table-compact w-full">
+                        <thead>
+                            <tr>
+                                <th>{"ID"}</th>
+                                <th>{"Size"}</th>
+                                <th>{"Created"}</th>
+                                <th>{"Expires"}</th>
+                                if show_downloads {
+                                    <th>{"Downloads"}</th>
+                                }
+                                <th></th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {share_rows}
+                        </tbody>
+                    </table>
+                    if shares.is_empty() {
+                        <div class="text-center mt-4">{"No shares yet."}</div>
+                    }
+                    if !error.is_empty() {
+                        <div class="alert alert-error mt-4">{&*error}</div>
+                    }
+                </div>
+            }
+        </NavBar>
+    }
+}
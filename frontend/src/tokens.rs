@@ -0,0 +1,299 @@
+use gloo_net::http::Request;
+use nanum_core::types::{ApiTokenCreationReq, ApiTokenCreationResp, ApiTokenInfo, TokenScope};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Event, HtmlInputElement, SubmitEvent};
+use yew::{
+    function_component, html, use_callback, use_effect_with_deps, use_state, Html, TargetCast,
+};
+
+use crate::{
+    error::{describe_error, error_message, read_error},
+    hooks::{use_user, UserStatus},
+    navbar::{NavBar, SignInPrompt},
+};
+
+const SCOPES: &[(TokenScope, &str)] = &[(TokenScope::Upload, "Upload files")];
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    js_sys::Date::new(&JsValue::from_f64(timestamp as f64 * 1000.))
+        .to_locale_string("default", &JsValue::UNDEFINED)
+        .into()
+}
+
+#[function_component(Tokens)]
+pub fn tokens() -> Html {
+    let user = use_user();
+
+    let error = use_state::<&'static str, _>(|| "");
+
+    let tokens = use_state::<Vec<ApiTokenInfo>, _>(Vec::new);
+    // Bumped to refetch the token list.
+    let reload = use_state(|| 0usize);
+
+    use_effect_with_deps(
+        {
+            let user = user.clone();
+            let error_state = error.clone();
+            let tokens = tokens.clone();
+            move |_| {
+                spawn_local(async move {
+                    let resp = match Request::get("/api/tokens").send().await {
+                        Ok(resp) => resp,
+                        Err(error) => {
+                            log::error!("failed to fetch tokens: {:?}", error);
+                            error_state.set("failed to fetch tokens");
+                            return;
+                        }
+                    };
+                    if resp.status() != 200 {
+                        log::error!("failed to fetch tokens. status code: {}", resp.status());
+                        let error = read_error(resp).await;
+                        if let Some(status) = UserStatus::from_error(error.as_ref()) {
+                            user.set(status);
+                            return;
+                        }
+                        error_state.set(describe_error(error.as_ref(), "failed to fetch tokens"));
+                        return;
+                    }
+                    let fetched_tokens: Vec<ApiTokenInfo> = match resp.json().await {
+                        Ok(resp) => resp,
+                        Err(error) => {
+                            log::error!("failed to read tokens response: {:?}", error);
+                            error_state.set("failed to read tokens response");
+                            return;
+                        }
+                    };
+                    tokens.set(fetched_tokens);
+                });
+                || ()
+            }
+        },
+        *reload,
+    );
+
+    let name = use_state(String::new);
+    let expires_in_days = use_state(|| 30u32);
+    let scopes = use_state(|| vec![TokenScope::Upload]);
+    let created_token = use_state::<Option<String>, _>(|| None);
+
+    let on_name_change = use_callback(
+        move |e: Event, name| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            name.set(input.value());
+        },
+        name.clone(),
+    );
+    let on_expires_in_days_change = use_callback(
+        move |e: Event, expires_in_days| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(days) = input.value().parse() {
+                expires_in_days.set(days);
+            }
+        },
+        expires_in_days.clone(),
+    );
+    let on_scope_change = use_callback(
+        move |(scope, e): (TokenScope, Event), scopes| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let mut new_scopes = (**scopes).clone();
+            new_scopes.retain(|s| *s != scope);
+            if input.checked() {
+                new_scopes.push(scope);
+            }
+            scopes.set(new_scopes);
+        },
+        scopes.clone(),
+    );
+
+    let onsubmit = use_callback(
+        move |e: SubmitEvent,
+              (error_state, name, expires_in_days, scopes, created_token, reload)| {
+            e.prevent_default();
+
+            if name.is_empty() || scopes.is_empty() {
+                return;
+            }
+
+            error_state.set("");
+            created_token.set(None);
+
+            let req = ApiTokenCreationReq {
+                name: (**name).clone(),
+                scopes: (**scopes).clone(),
+                expires_in_days: **expires_in_days,
+            };
+            let error_state = error_state.clone();
+            let created_token = created_token.clone();
+            let reload = reload.clone();
+            spawn_local(async move {
+                let req = match Request::post("/api/tokens").json(&req) {
+                    Ok(req) => req,
+                    Err(error) => {
+                        log::error!("failed to make request: {:?}", error);
+                        error_state.set("failed to make request");
+                        return;
+                    }
+                };
+                let resp = match req.send().await {
+                    Ok(resp) => resp,
+                    Err(error) => {
+                        log::error!("failed to create token: {:?}", error);
+                        error_state.set("failed to create token");
+                        return;
+                    }
+                };
+                if resp.status() != 200 {
+                    log::error!("failed to create token. status code: {}", resp.status());
+                    error_state.set(error_message(resp, "failed to create token").await);
+                    return;
+                }
+                let resp: ApiTokenCreationResp = match resp.json().await {
+                    Ok(resp) => resp,
+                    Err(error) => {
+                        log::error!("failed to read response body: {:?}", error);
+                        error_state.set("failed to read response body");
+                        return;
+                    }
+                };
+                created_token.set(Some(resp.token));
+                reload.set(*reload + 1);
+            });
+        },
+        (
+            error.clone(),
+            name,
+            expires_in_days.clone(),
+            scopes.clone(),
+            created_token.clone(),
+            reload.clone(),
+        ),
+    );
+
+    let on_delete = use_callback(
+        move |id: String, (error_state, reload)| {
+            error_state.set("");
+
+            let error_state = error_state.clone();
+            let reload = reload.clone();
+            spawn_local(async move {
+                let resp = match Request::delete(&format!("/api/tokens/{id}")).send().await {
+                    Ok(resp) => resp,
+                    Err(error) => {
+                        log::error!("failed to delete token: {:?}", error);
+                        error_state.set("failed to delete token");
+                        return;
+                    }
+                };
+                if resp.status() != 200 {
+                    log::error!("failed to delete token. status code: {}", resp.status());
+                    error_state.set(error_message(resp, "failed to delete token").await);
+                    return;
+                }
+                reload.set(*reload + 1);
+            });
+        },
+        (error.clone(), reload),
+    );
+
+    let token_rows = tokens
+        .iter()
+        .map(|token| {
+            let scopes = token
+                .scopes
+                .iter()
+                .filter_map(|scope| SCOPES.iter().find(|(s, _)| s == scope))
+                .map(|(_, label)| *label)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let onclick = {
+                let id = token.id.clone();
+                on_delete.reform(move |_| id.clone())
+            };
+            html! {
+                <tr key={token.id.clone()}>
+                    <td>{&token.name}</td>
+                    <td>{scopes}</td>
+                    <td>{format_timestamp(token.created_at)}</td>
+                    <td>{format_timestamp(token.expires_at)}</td>
+                    <td>{token.last_used_at.map(format_timestamp).unwrap_or_else(|| "Never".to_string())}</td>
+                    <td><button class="btn btn-error btn-xs" {onclick}>{"Delete"}</button></td>
+                </tr>
+            }
+        })
+        .collect::<Html>();
+
+    let scope_checkboxes = SCOPES
+        .iter()
+        .map(|(scope, label)| {
+            let scope = *scope;
+            let onchange = on_scope_change.reform(move |e: Event| (scope, e));
+            html! {
+                <label class="label cursor-pointer justify-start gap-2">
+                    <input
+                        type="checkbox"
+                        class="checkbox"
+                        checked={scopes.contains(&scope)}
+                        {onchange}
+                    />
+                    <span class="label-text">{*label}</span>
+                </label>
+            }
+        })
+        .collect::<Html>();
+
+    html! {
+        <NavBar user={user.email()}>
+            if matches!(*user, UserStatus::SignedOut | UserStatus::Forbidden) {
+                <SignInPrompt status={(*user).clone()} />
+            } else {
+                <div class="w-full max-w-3xl">
+                    <table class="table table-compact w-full mb-8">
+                        <thead>
+                            <tr>
+                                <th>{"Name"}</th>
+                                <th>{"Scopes"}</th>
+                                <th>{"Created"}</th>
+                                <th>{"Expires"}</th>
+                                <th>{"Last used"}</th>
+                                <th></th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {token_rows}
+                        </tbody>
+                    </table>
+                    <form class="form-control w-full max-w-xs mx-auto" {onsubmit}>
+                        <label class="label label-text">{"Token name"}</label>
+                        <input
+                            type="text"
+                            class="input input-bordered w-full"
+                            onchange={on_name_change}
+                        />
+                        <label class="label label-text">{"Expires in (days)"}</label>
+                        <input
+                            type="number"
+                            min="1"
+                            max="365"
+                            value={expires_in_days.to_string()}
+                            class="input input-bordered w-full"
+                            onchange={on_expires_in_days_change}
+                        />
+                        <label class="label label-text">{"Scopes"}</label>
+                        {scope_checkboxes}
+                        <input type="submit" class="btn mt-4" value="Create token" />
+                    </form>
+                    if let Some(token) = &*created_token {
+                        <div class="alert mt-4 flex-col items-start">
+                            <span>{"Copy the token now. It will not be shown again."}</span>
+                            <code class="break-all">{token}</code>
+                        </div>
+                    }
+                    if !error.is_empty() {
+                        <div class="alert alert-error mt-4">{&*error}</div>
+                    }
+                </div>
+            }
+        </NavBar>
+    }
+}
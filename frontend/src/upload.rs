@@ -1,4 +1,5 @@
 use aead::stream::EncryptorBE32;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chacha20poly1305::{
     aead::{generic_array::GenericArray, Aead},
     Key, KeyInit, XChaCha20Poly1305,
@@ -7,19 +8,43 @@ use futures_util::{StreamExt, TryStreamExt};
 use gloo_net::http::Request;
 use hkdf::Hkdf;
 use js_sys::Uint8Array;
-use nanum_core::types::MetadataCreationReq;
+use nanum_core::{
+    id::MAX_ID_LENGTH,
+    key_check, key_slot,
+    padding::PaddingScheme,
+    passphrase::{self, DEFAULT_WORD_COUNT},
+    recipient::{parse_recipients, FILE_KEY_LENGTH, MAX_RECIPIENTS},
+    types::{EncryptedFileInfo, FileInfo, MetadataCreationReq},
+};
 use serde::Deserialize;
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::spawn_local;
-use web_sys::{Event, File, HtmlInputElement, SubmitEvent};
-use yew::{
-    function_component, html, use_callback, use_effect_with_deps, use_state, Html, TargetCast,
+use web_sys::{
+    Event, File, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement, InputEvent, MouseEvent,
+    SubmitEvent,
 };
+use yew::{classes, function_component, html, use_callback, use_state, Html, TargetCast};
 
-use crate::{navbar::NavBar, types::User};
+use crate::{
+    error::{describe_error, read_error},
+    hooks::{use_client_config, use_user, UserStatus},
+    navbar::{NavBar, SignInPrompt},
+    passphrase::{is_strong_enough, PassphraseStrength},
+};
 
 const BLOCK_SIZE: usize = 1024 * 1024 * 10;
+/// Keeps encrypted file info well under the limit of the server.
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum KeyMode {
+    Passphrase,
+    /// A random key carried in the link fragment
+    GeneratedKey,
+    /// A random key wrapped to recipient public keys
+    Recipients,
+}
 
 #[derive(Deserialize)]
 struct PostMetadataResp {
@@ -28,48 +53,24 @@ struct PostMetadataResp {
 
 #[function_component(Upload)]
 pub fn upload() -> Html {
-    let user = use_state(String::new);
-
-    use_effect_with_deps(
-        {
-            let user = user.clone();
-            move |_| {
-                spawn_local(async move {
-                    let resp = match Request::get("/api/user").send().await {
-                        Ok(resp) => resp,
-                        Err(error) => {
-                            log::error!("failed to fetch user: {:?}", error);
-                            return;
-                        }
-                    };
-                    if resp.status() != 200 {
-                        log::error!("failed to fetch user. status code: {}", resp.status());
-                        return;
-                    }
-                    let fetched_user: User = match resp.json().await {
-                        Ok(resp) => resp,
-                        Err(error) => {
-                            log::error!("failed to read user response: {:?}", error);
-                            return;
-                        }
-                    };
-                    user.set(fetched_user.primary_email);
-                });
-                || ()
-            }
-        },
-        (),
-    );
+    let user = use_user();
+    let config = use_client_config();
+    let min_entropy = config.as_ref().map(|config| config.min_passphrase_entropy);
 
     let error = use_state::<&'static str, _>(|| "");
 
     let file = use_state::<Option<File>, _>(|| None);
     let id = use_state(String::new);
     let passphrase = use_state(String::new);
+    let show_passphrase = use_state(|| false);
+    let key_mode = use_state(|| KeyMode::Passphrase);
+    let recipients = use_state(String::new);
+    let description = use_state(String::new);
+    let hide_size = use_state(|| true);
 
     let upload_started = use_state(|| false);
     let progress = use_state(|| 0usize);
-    let finished_id = use_state::<Option<String>, _>(|| None);
+    let finished_link = use_state::<Option<String>, _>(|| None);
 
     let on_file_change = use_callback(
         move |e: Event, file| {
@@ -83,33 +84,176 @@ pub fn upload() -> Html {
         file.clone(),
     );
     let on_id_change = use_callback(
-        move |e: Event, id| {
+        move |e: InputEvent, id| {
             let input: HtmlInputElement = e.target_unchecked_into();
             id.set(input.value());
         },
         id.clone(),
     );
     let on_passphrase_change = use_callback(
-        move |e: Event, passphrase| {
+        move |e: InputEvent, passphrase| {
             let input: HtmlInputElement = e.target_unchecked_into();
             passphrase.set(input.value());
         },
         passphrase.clone(),
     );
+    let on_generate_passphrase = use_callback(
+        move |_: MouseEvent, (error_state, passphrase, show_passphrase)| match passphrase::generate(
+            DEFAULT_WORD_COUNT,
+        ) {
+            Ok(generated) => {
+                passphrase.set(generated);
+                show_passphrase.set(true);
+            }
+            Err(error) => {
+                log::error!("cannot generate passphrase: {:?}", error);
+                error_state.set("cannot generate passphrase");
+            }
+        },
+        (error.clone(), passphrase.clone(), show_passphrase.clone()),
+    );
+    let on_description_change = use_callback(
+        move |e: Event, description| {
+            let input: HtmlTextAreaElement = e.target_unchecked_into();
+            description.set(input.value());
+        },
+        description.clone(),
+    );
+    let on_hide_size_change = use_callback(
+        move |e: Event, hide_size| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            hide_size.set(input.checked());
+        },
+        hide_size.clone(),
+    );
+    let on_key_mode_change = use_callback(
+        move |e: Event, key_mode| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            key_mode.set(match select.value().as_str() {
+                "generated_key" => KeyMode::GeneratedKey,
+                "recipients" => KeyMode::Recipients,
+                _ => KeyMode::Passphrase,
+            });
+        },
+        key_mode.clone(),
+    );
+    let on_recipients_change = use_callback(
+        move |e: Event, recipients| {
+            let input: HtmlTextAreaElement = e.target_unchecked_into();
+            recipients.set(input.value());
+        },
+        recipients.clone(),
+    );
 
     let onsubmit = use_callback(
         move |e: SubmitEvent,
-              (error_state, file, id, passphrase, upload_started, progress, finished_id)| {
+              (
+            user,
+            error_state,
+            file,
+            id,
+            (passphrase, min_entropy),
+            key_mode,
+            recipients,
+            description,
+            hide_size,
+            upload_started,
+            progress,
+            finished_link,
+        )| {
             e.prevent_default();
 
-            if **upload_started || file.is_none() || passphrase.is_empty() {
+            if **upload_started
+                || file.is_none()
+                || (**key_mode == KeyMode::Passphrase && passphrase.is_empty())
+            {
+                return;
+            }
+            if !id.is_empty() && nanum_core::id::validate(id).is_err() {
+                return;
+            }
+            if **key_mode == KeyMode::Passphrase && !is_strong_enough(passphrase, *min_entropy) {
+                error_state.set("Passphrase is too weak. Make it longer or generate one.");
                 return;
             }
 
             error_state.set("");
             progress.set(0);
-            finished_id.set(None);
-            upload_started.set(true);
+            finished_link.set(None);
+
+            // generate a random file key, which the encryption key is derived from. it is
+            // wrapped under the passphrase or to the recipients, so that more passphrases can be
+            // added later.
+            let mut file_key = [0u8; FILE_KEY_LENGTH];
+            if let Err(error) = getrandom::getrandom(&mut file_key) {
+                log::error!("cannot generate random key: {:?}", error);
+                error_state.set("cannot generate random key");
+                return;
+            }
+
+            let mut generated_key = None;
+            let mut key_slots = Vec::new();
+            let mut wrapped_keys = Vec::new();
+            match **key_mode {
+                KeyMode::Passphrase => {
+                    match key_slot::wrap("default".to_string(), passphrase, &file_key) {
+                        Ok(slot) => key_slots.push(slot),
+                        Err(error) => {
+                            log::error!("cannot wrap key under passphrase: {:?}", error);
+                            error_state.set("cannot wrap key under passphrase");
+                            return;
+                        }
+                    }
+                }
+                KeyMode::GeneratedKey => {
+                    // generate a random key to be carried in the link fragment, which is never
+                    // sent to the server
+                    let mut key = [0u8; 32];
+                    if let Err(error) = getrandom::getrandom(&mut key) {
+                        log::error!("cannot generate random key: {:?}", error);
+                        error_state.set("cannot generate random key");
+                        return;
+                    }
+                    let key = URL_SAFE_NO_PAD.encode(key);
+                    match key_slot::wrap("link".to_string(), &key, &file_key) {
+                        Ok(slot) => key_slots.push(slot),
+                        Err(error) => {
+                            log::error!("cannot wrap key under passphrase: {:?}", error);
+                            error_state.set("cannot wrap key under passphrase");
+                            return;
+                        }
+                    }
+                    generated_key = Some(key);
+                }
+                KeyMode::Recipients => {
+                    let recipients = match parse_recipients(recipients) {
+                        Ok(recipients) => recipients,
+                        Err(error) => {
+                            error_state.set(error.message());
+                            return;
+                        }
+                    };
+                    if recipients.is_empty() {
+                        error_state.set("at least one recipient is required");
+                        return;
+                    }
+                    if recipients.len() > MAX_RECIPIENTS {
+                        error_state.set("too many recipients");
+                        return;
+                    }
+
+                    for recipient in &recipients {
+                        match recipient.wrap(&file_key) {
+                            Ok(wrapped_key) => wrapped_keys.push(wrapped_key),
+                            Err(error) => {
+                                log::error!("cannot wrap key to recipient: {:?}", error);
+                                error_state.set("cannot wrap key to recipient");
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
 
             let file = file.as_ref().unwrap();
             let file_size = file.size() as usize;
@@ -125,7 +269,7 @@ pub fn upload() -> Html {
             }
 
             // generate key by hkdf
-            let h = Hkdf::<Sha256>::new(Some(&salt), passphrase.as_bytes());
+            let h = Hkdf::<Sha256>::new(Some(&salt), &file_key);
             let mut key_slice = [0u8; 32];
             if let Err(err) = h.expand(&[], &mut key_slice[..]) {
                 log::error!("cannot expand passphrase by hkdf: {:?}", err);
@@ -146,6 +290,12 @@ pub fn upload() -> Html {
                 error_state.set("cannot get random nonce value");
                 return;
             }
+            let mut info_nonce = [0u8; 24];
+            if let Err(err) = getrandom::getrandom(&mut info_nonce) {
+                log::error!("cannot get random nonce value: {:?}", err);
+                error_state.set("cannot get random nonce value");
+                return;
+            }
 
             let key = Key::from_slice(&key_slice);
             let cipher = XChaCha20Poly1305::new(key);
@@ -184,24 +334,47 @@ pub fn upload() -> Html {
                 .and_then(|b| async move { b.dyn_into::<Uint8Array>() })
                 .map_ok(|arr| arr.to_vec());
 
+            // pad with zeros after the file, so that the server only sees the padded size
+            let padding = hide_size.then_some(PaddingScheme::Padme);
+            let padded_size = padding.map_or(file_size, |padding| padding.padded_len(file_size));
+            let padding_len = padded_size - file_size;
+            let padding_chunks = futures_util::stream::iter(
+                (0..padding_len)
+                    .step_by(BLOCK_SIZE)
+                    .map(move |start| Ok(vec![0u8; (padding_len - start).min(BLOCK_SIZE)])),
+            );
+            let fut = fut.chain(padding_chunks);
+
             let mut fut = Box::pin(fut);
 
             let metadata = MetadataCreationReq {
                 salt: salt.to_vec(),
+                key_check: Some(key_check::derive(&salt, &file_key).to_vec()),
                 nonce: stream_nonce.to_vec(),
                 filename_nonce: filename_nonce.to_vec(),
                 filename: encrypted_filename,
-                size: file_size,
+                size: padded_size,
                 block_size: BLOCK_SIZE,
+                padding,
+                recipients: wrapped_keys,
+                key_slots,
             };
 
             let stream_nonce = *stream_nonce;
 
+            // hashed while the file is read, and encrypted once every chunk is uploaded
+            let mime_type = Some(file.type_()).filter(|mime_type| !mime_type.is_empty());
+            let modified_at = Some((file.last_modified() / 1000.) as i64);
+            let description =
+                Some(description.trim().to_string()).filter(|description| !description.is_empty());
+            let info_cipher = cipher.clone();
+
+            let user = user.clone();
             let error_state = error_state.clone();
             let id = id.clone();
             let upload_started = upload_started.clone();
             let progress = progress.clone();
-            let finished_id = finished_id.clone();
+            let finished_link = finished_link.clone();
             // core logic of streaming upload / encryption
             let encrypt_routine = async move {
                 // use stream encryptor
@@ -232,7 +405,12 @@ pub fn upload() -> Html {
 
                 if resp.status() != 200 {
                     log::error!("failed to upload metadata. status code: {}", resp.status());
-                    error_state.set("failed to upload metadata");
+                    let error = read_error(resp).await;
+                    if let Some(status) = UserStatus::from_error(error.as_ref()) {
+                        user.set(status);
+                        return;
+                    }
+                    error_state.set(describe_error(error.as_ref(), "failed to upload metadata"));
                     return;
                 }
 
@@ -245,6 +423,8 @@ pub fn upload() -> Html {
                     }
                 };
 
+                let mut hasher = Sha256::new();
+                let mut hashed_bytes = 0;
                 let mut progress_bytes = 0;
                 let mut seq: i64 = 1;
                 let mut buffer = Vec::<u8>::with_capacity(BLOCK_SIZE);
@@ -259,6 +439,10 @@ pub fn upload() -> Html {
                         }
                     };
                     let mut v: &[u8] = v.as_ref();
+                    // hash only the file, not the padding after it
+                    let hash_len = (file_size - hashed_bytes).min(v.len());
+                    hasher.update(&v[..hash_len]);
+                    hashed_bytes += hash_len;
                     // divide inputs into fixed block size
                     while buffer.len() + v.len() >= BLOCK_SIZE {
                         let split_idx = BLOCK_SIZE - buffer.len();
@@ -274,8 +458,6 @@ pub fn upload() -> Html {
                                 return;
                             }
                         };
-                        let chunk_len = chunk.len();
-
                         let chunk: Uint8Array = chunk.as_slice().into();
                         let resp = match Request::post(&format!("/api/file/{id}/{seq}"))
                             .body(chunk)
@@ -292,7 +474,13 @@ pub fn upload() -> Html {
 
                         if resp.status() != 200 {
                             log::error!("failed to upload chunk. status code: {}", resp.status());
-                            error_state.set("failed to upload chunk");
+                            let error = read_error(resp).await;
+                            if let Some(status) = UserStatus::from_error(error.as_ref()) {
+                                user.set(status);
+                                return;
+                            }
+                            error_state
+                                .set(describe_error(error.as_ref(), "failed to upload chunk"));
                             return;
                         }
 
@@ -300,8 +488,9 @@ pub fn upload() -> Html {
                         v = &v[split_idx..];
                         seq += 1;
 
-                        progress_bytes += chunk_len;
-                        progress.set(progress_bytes);
+                        // progress is of the file, not of the padding after it
+                        progress_bytes += BLOCK_SIZE;
+                        progress.set(progress_bytes.min(file_size));
                     }
                     buffer.extend(v);
                 }
@@ -332,27 +521,99 @@ pub fn upload() -> Html {
 
                 if resp.status() != 200 {
                     log::error!("failed to upload chunk. status code: {}", resp.status());
-                    error_state.set("failed to upload chunk");
+                    let error = read_error(resp).await;
+                    if let Some(status) = UserStatus::from_error(error.as_ref()) {
+                        user.set(status);
+                        return;
+                    }
+                    error_state.set(describe_error(error.as_ref(), "failed to upload chunk"));
+                    return;
+                }
+
+                // encrypt and upload file info
+                let info = FileInfo {
+                    size: padding.map(|_| file_size),
+                    mime_type,
+                    modified_at,
+                    sha256: hasher.finalize().to_vec(),
+                    description,
+                };
+                let info = match serde_json::to_vec(&info) {
+                    Ok(info) => info,
+                    Err(error) => {
+                        log::error!("failed to serialize file info: {:?}", error);
+                        error_state.set("failed to serialize file info");
+                        return;
+                    }
+                };
+                let info_nonce = GenericArray::from_slice(info_nonce.as_ref());
+                let info = match info_cipher.encrypt(info_nonce, info.as_slice()) {
+                    Ok(encrypted) => EncryptedFileInfo {
+                        nonce: info_nonce.to_vec(),
+                        ciphertext: encrypted,
+                    },
+                    Err(err) => {
+                        log::error!("failed to encrypt file info: {:?}", err);
+                        error_state.set("failed to encrypt file info");
+                        return;
+                    }
+                };
+                let req = match Request::post(&format!("/api/metadata/{id}/complete")).json(&info) {
+                    Ok(req) => req,
+                    Err(error) => {
+                        log::error!("failed to make request: {:?}", error);
+                        error_state.set("failed to make request");
+                        return;
+                    }
+                };
+                let resp = match req.send().await {
+                    Ok(resp) => resp,
+                    Err(error) => {
+                        log::error!("failed to upload file info: {:?}", error);
+                        error_state.set("failed to upload file info");
+                        return;
+                    }
+                };
+                if resp.status() != 200 {
+                    log::error!("failed to upload file info. status code: {}", resp.status());
+                    let error = read_error(resp).await;
+                    if let Some(status) = UserStatus::from_error(error.as_ref()) {
+                        user.set(status);
+                        return;
+                    }
+                    error_state.set(describe_error(error.as_ref(), "failed to upload file info"));
                     return;
                 }
 
                 progress.set(file_size);
-                finished_id.set(Some(id));
-                upload_started.set(false);
+                finished_link.set(Some(match generated_key {
+                    Some(key) => format!("/{id}#key={key}"),
+                    None => format!("/{id}"),
+                }));
             };
 
             // spawn entire routine in promise
             // TODO: research Web Workers and try to gain more performance
-            spawn_local(encrypt_routine);
+            upload_started.set(true);
+            spawn_local(async move {
+                encrypt_routine.await;
+                // whether it finished or failed, allow another upload
+                upload_started.set(false);
+            });
         },
         (
+            user.clone(),
             error.clone(),
             file.clone(),
-            id,
-            passphrase,
+            id.clone(),
+            (passphrase.clone(), min_entropy),
+            key_mode.clone(),
+            recipients.clone(),
+            description.clone(),
+            hide_size.clone(),
             upload_started.clone(),
             progress.clone(),
-            finished_id.clone(),
+            finished_link.clone(),
         ),
     );
 
@@ -367,51 +628,112 @@ pub fn upload() -> Html {
         }
         _ => html! { <></> },
     };
-    let finished_id_show = if let Some(id) = &*finished_id {
+    let finished_link_show = if let Some(link) = &*finished_link {
         html! {
-            <div class="mt-4">
+            <div class="mt-4 break-all">
                 {"Uploaded to "}
-                <a href={format!("/{id}")} class="link link-primary">{format!("/{id}")}</a>
+                <a href={link.clone()} class="link link-primary">{link}</a>
             </div>
         }
     } else {
         html! { <></> }
     };
 
+    let id_error = if id.is_empty() {
+        None
+    } else {
+        nanum_core::id::validate(&id).err()
+    };
+
     html! {
-        <NavBar user={(*user).clone()}>
+        <NavBar user={user.email()}>
             <div class="max-w-xs">
                 <div class="w-full flex justify-center mb-4">
                     <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="w-6 h-6">
                       <path fill-rule="evenodd" d="M10.5 3.75a6 6 0 00-5.98 6.496A5.25 5.25 0 006.75 20.25H18a4.5 4.5 0 002.206-8.423 3.75 3.75 0 00-4.133-4.303A6.001 6.001 0 0010.5 3.75zm2.03 5.47a.75.75 0 00-1.06 0l-3 3a.75.75 0 101.06 1.06l1.72-1.72v4.94a.75.75 0 001.5 0v-4.94l1.72 1.72a.75.75 0 101.06-1.06l-3-3z" clip-rule="evenodd" />
                     </svg>
                 </div>
-                <form class="form-control w-full" {onsubmit}>
-                    <label class="label label-text">{"File to upload"}</label>
-                    <input
-                        type="file"
-                        class="file-input file-input-bordered w-full"
-                        onchange={on_file_change}
-                    />
-                    <label class="label label-text">{"ID"}</label>
-                    <input
-                        type="text"
-                        placeholder="<random>"
-                        class="input input-bordered w-full"
-                        onchange={on_id_change}
-                    />
-                    <label class="label label-text">{"Passphrase"}</label>
-                    <input
-                        type="password"
-                        class="input input-bordered w-full"
-                        onchange={on_passphrase_change}
-                    />
-                    if !*upload_started {
-                        <input type="submit" class="btn mt-4" value="Upload" />
-                    }
-                </form>
+                if matches!(*user, UserStatus::SignedOut | UserStatus::Forbidden) {
+                    <SignInPrompt status={(*user).clone()} />
+                } else {
+                    <form class="form-control w-full" {onsubmit}>
+                        <label class="label label-text">{"File to upload"}</label>
+                        <input
+                            type="file"
+                            class="file-input file-input-bordered w-full"
+                            onchange={on_file_change}
+                        />
+                        <label class="label label-text">{"ID"}</label>
+                        <input
+                            type="text"
+                            placeholder="<random>"
+                            maxlength={MAX_ID_LENGTH.to_string()}
+                            class={classes!("input", "input-bordered", "w-full", id_error.map(|_| "input-error"))}
+                            oninput={on_id_change}
+                        />
+                        if let Some(id_error) = id_error {
+                            <label class="label">
+                                <span class="label-text-alt text-error">{id_error.message()}</span>
+                            </label>
+                        }
+                        <label class="label label-text">{"Encryption"}</label>
+                        <select class="select select-bordered w-full" onchange={on_key_mode_change}>
+                            <option value="passphrase" selected={*key_mode == KeyMode::Passphrase}>
+                                {"Passphrase"}
+                            </option>
+                            <option value="generated_key" selected={*key_mode == KeyMode::GeneratedKey}>
+                                {"Generate a key and include it in the link"}
+                            </option>
+                            <option value="recipients" selected={*key_mode == KeyMode::Recipients}>
+                                {"Recipient public keys"}
+                            </option>
+                        </select>
+                        if *key_mode == KeyMode::Passphrase {
+                            <label class="label label-text">{"Passphrase"}</label>
+                            <div class="flex gap-2">
+                                <input
+                                    type={if *show_passphrase { "text" } else { "password" }}
+                                    class="input input-bordered w-full"
+                                    value={(*passphrase).clone()}
+                                    oninput={on_passphrase_change}
+                                />
+                                <button type="button" class="btn" onclick={on_generate_passphrase}>
+                                    {"Generate"}
+                                </button>
+                            </div>
+                            <PassphraseStrength passphrase={(*passphrase).clone()} {min_entropy} />
+                        }
+                        if *key_mode == KeyMode::Recipients {
+                            <label class="label label-text">{"Recipients"}</label>
+                            <textarea
+                                class="textarea textarea-bordered w-full font-mono"
+                                placeholder="age1... (one per line)"
+                                value={(*recipients).clone()}
+                                onchange={on_recipients_change}
+                            />
+                        }
+                        <label class="label cursor-pointer">
+                            <span class="label-text">{"Hide the exact file size"}</span>
+                            <input
+                                type="checkbox"
+                                class="checkbox"
+                                checked={*hide_size}
+                                onchange={on_hide_size_change}
+                            />
+                        </label>
+                        <label class="label label-text">{"Description (optional)"}</label>
+                        <textarea
+                            class="textarea textarea-bordered w-full"
+                            maxlength={MAX_DESCRIPTION_LENGTH.to_string()}
+                            onchange={on_description_change}
+                        />
+                        if !*upload_started {
+                            <input type="submit" class="btn mt-4" value="Upload" />
+                        }
+                    </form>
+                }
                 {progress_show}
-                {finished_id_show}
+                {finished_link_show}
                 if !error.is_empty() {
                     <div class="alert alert-error mt-4">{&*error}</div>
                 }